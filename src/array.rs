@@ -1,16 +1,28 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
 use std::mem::{self, MaybeUninit};
-use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::ops::{Bound, Deref, DerefMut, Index, IndexMut, RangeBounds};
+use std::ptr;
+use std::slice;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Array<T: Copy, const N: usize> {
+// only the first len elements of inner are initialized
+pub struct Array<T, const N: usize> {
     len: usize,
-    inner: [T; N],
+    inner: [MaybeUninit<T>; N],
 }
 
-impl<T: Copy, const N: usize> Array<T, N> {
+fn uninit<T, const N: usize>() -> [MaybeUninit<T>; N] {
+    // an array of MaybeUninit does not need initializing
+    unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() }
+}
+
+impl<T, const N: usize> Array<T, N> {
     pub fn new() -> Self {
-        let inner = unsafe { MaybeUninit::uninit().assume_init() };
-        Self { inner, len: 0 }
+        Self {
+            inner: uninit(),
+            len: 0,
+        }
     }
 
     pub fn capacity(&self) -> usize {
@@ -27,158 +39,361 @@ impl<T: Copy, const N: usize> Array<T, N> {
 
     pub fn reserve_exact<const M: usize>(mut self) -> Array<T, M> {
         assert!(M >= N, "cannot reserve into an Array of a smaller capacity");
-        let len = self.len;
-        let mut inner = unsafe { MaybeUninit::<[T; M]>::uninit().assume_init() };
-        for i in 0..len {
-            mem::swap(&mut inner[i], &mut self.inner[i]);
+        let mut array = Array::new();
+        unsafe {
+            ptr::copy_nonoverlapping(self.as_ptr(), array.as_mut_ptr(), self.len);
         }
-        mem::forget(self);
-        Array { inner, len }
+        array.len = self.len;
+        self.len = 0;
+        array
     }
 
     pub fn truncate(&mut self, len: usize) {
-        for i in len..self.len {
-            let mut t = unsafe { MaybeUninit::uninit().assume_init() };
-            mem::swap(&mut t, &mut self.inner[i]);
-            mem::forget(t);
+        if len >= self.len {
+            return;
+        }
+        let tail = self.len - len;
+        self.len = len;
+        unsafe {
+            let tail = slice::from_raw_parts_mut(self.as_mut_ptr().add(len), tail);
+            ptr::drop_in_place(tail);
         }
-        self.len = self.len.min(len);
     }
 
     pub fn as_slice(&self) -> &[T] {
-        &self.inner[..self.len]
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
     }
 
     pub fn as_mut_slice(&mut self) -> &mut [T] {
-        &mut self.inner[..self.len]
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
     }
 
     pub fn as_ptr(&self) -> *const T {
-        &self.inner[0] as *const _
+        self.inner.as_ptr() as *const T
     }
 
     pub fn as_mut_ptr(&mut self) -> *mut T {
-        &mut self.inner[0] as *mut _
+        self.inner.as_mut_ptr() as *mut T
     }
 
     pub fn swap_remove(&mut self, index: usize) -> T {
-        assert!(self.len > 0, "attempt to remove from an empty array");
+        assert!(
+            index < self.len,
+            "swap_remove index is {:?} but len is {:?}",
+            index,
+            self.len
+        );
         self.len -= 1;
-        let mut t = unsafe { MaybeUninit::uninit().assume_init() };
-        mem::swap(&mut t, &mut self.inner[self.len]);
-        mem::swap(&mut t, &mut self.inner[index]);
-        t
+        unsafe {
+            let last = ptr::read(self.as_ptr().add(self.len));
+            let hole = self.as_mut_ptr().add(index);
+            if index == self.len {
+                last
+            } else {
+                ptr::replace(hole, last)
+            }
+        }
     }
 
-    pub fn insert(&mut self, index: usize, mut element: T) {
-        let mut t = unsafe { MaybeUninit::uninit().assume_init() };
-        mem::swap(&mut t, &mut self.inner[index]);
-        mem::swap(&mut self.inner[index], &mut element);
-        mem::forget(element);
-        self.len += 1;
-        for i in index + 1..self.len {
-            let mut u = unsafe { MaybeUninit::uninit().assume_init() };
-            mem::swap(&mut u, &mut self.inner[i]);
-            mem::swap(&mut self.inner[i], &mut t);
-            mem::swap(&mut t, &mut u);
-            mem::forget(u);
+    pub fn insert(&mut self, index: usize, element: T) {
+        assert!(
+            index <= self.len,
+            "insertion index is {:?} but len is {:?}",
+            index,
+            self.len
+        );
+        assert!(
+            self.len < N,
+            "inserting would result in exceeding the capacity, which is {:?}",
+            N
+        );
+        unsafe {
+            let p = self.as_mut_ptr().add(index);
+            ptr::copy(p, p.add(1), self.len - index);
+            ptr::write(p, element);
         }
+        self.len += 1;
     }
 
     pub fn remove(&mut self, index: usize) -> T {
-        assert!(self.len > 0, "attempt to remove from an empty array");
-        let mut t = unsafe { MaybeUninit::uninit().assume_init() };
-        mem::swap(&mut t, &mut self.inner[index]);
+        assert!(
+            index < self.len,
+            "removal index is {:?} but len is {:?}",
+            index,
+            self.len
+        );
         self.len -= 1;
-        for i in index..self.len {
-            self.inner.swap(i, i + 1);
+        unsafe {
+            let p = self.as_mut_ptr().add(index);
+            let t = ptr::read(p);
+            ptr::copy(p.add(1), p, self.len - index);
+            t
         }
-        t
     }
 
-    pub fn push(&mut self, mut value: T) {
+    pub fn push(&mut self, value: T) {
         assert!(
             self.len < N,
             "pushing would result in exceeding the capacity, which is {:?}",
             N
         );
-
-        mem::swap(&mut self.inner[self.len], &mut value);
-        mem::forget(value);
+        self.inner[self.len] = MaybeUninit::new(value);
         self.len += 1;
     }
 
+    // gives the value back if the array is full
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        if self.len < N {
+            self.push(value);
+            Ok(())
+        } else {
+            Err(value)
+        }
+    }
+
     pub fn pop(&mut self) -> Option<T> {
         if self.len == 0 {
             None
         } else {
-            let mut t = unsafe { MaybeUninit::uninit().assume_init() };
             self.len -= 1;
-            mem::swap(&mut t, &mut self.inner[self.len]);
-            Some(t)
+            Some(unsafe { ptr::read(self.as_ptr().add(self.len)) })
         }
     }
 
     pub fn clear(&mut self) {
-        for i in 0..self.len {
-            let mut t = unsafe { MaybeUninit::uninit().assume_init() };
-            mem::swap(&mut t, &mut self.inner[i]);
-            mem::drop(t);
-        }
+        self.truncate(0);
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let len = self.len;
+        // a panicking f leaks the elements it has not seen yet
         self.len = 0;
+        let mut kept = 0;
+        for i in 0..len {
+            unsafe {
+                let p = self.as_mut_ptr();
+                if f(&*p.add(i)) {
+                    if kept != i {
+                        ptr::copy_nonoverlapping(p.add(i), p.add(kept), 1);
+                    }
+                    kept += 1;
+                    self.len = kept;
+                } else {
+                    ptr::drop_in_place(p.add(i));
+                }
+            }
+        }
+        self.len = kept;
+    }
+
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, N> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "drain range is {:?}..{:?} but len is {:?}",
+            start,
+            end,
+            self.len
+        );
+        let tail = self.len;
+        // a leaked Drain leaks the tail instead of exposing drained elements
+        self.len = start;
+        Drain {
+            array: self,
+            start,
+            idx: start,
+            end,
+            tail,
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for Array<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Default for Array<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for Array<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for Array<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
     }
 }
 
-impl<T: Copy, const N: usize> IntoIterator for Array<T, N> {
+impl<T: PartialEq, const N: usize> PartialEq for Array<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for Array<T, N> {}
+
+impl<T: Hash, const N: usize> Hash for Array<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state);
+    }
+}
+
+impl<T, const N: usize> Extend<T> for Array<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for t in iter {
+            self.push(t);
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for Array<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut array = Array::new();
+        array.extend(iter);
+        array
+    }
+}
+
+impl<T, const N: usize> IntoIterator for Array<T, N> {
     type Item = T;
     type IntoIter = IntoIter<T, N>;
 
     fn into_iter(mut self) -> IntoIter<T, N> {
         let len = self.len;
-        let mut inner = unsafe { MaybeUninit::uninit().assume_init() };
-        mem::swap(&mut inner, &mut self.inner);
-        mem::forget(self);
+        self.len = 0;
+        let inner = mem::replace(&mut self.inner, uninit());
         IntoIter { idx: 0, len, inner }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct IntoIter<T: Copy, const N: usize> {
+pub struct IntoIter<T, const N: usize> {
     idx: usize,
     len: usize,
-    inner: [T; N],
+    inner: [MaybeUninit<T>; N],
+}
+
+impl<T, const N: usize> IntoIter<T, N> {
+    pub fn as_slice(&self) -> &[T] {
+        unsafe {
+            let p = (self.inner.as_ptr() as *const T).add(self.idx);
+            slice::from_raw_parts(p, self.len - self.idx)
+        }
+    }
 }
 
-impl<T: Copy, const N: usize> Iterator for IntoIter<T, N> {
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.idx == self.len {
             None
         } else {
-            let mut t = unsafe { MaybeUninit::uninit().assume_init() };
-            mem::swap(&mut t, &mut self.inner[self.idx]);
+            let t = unsafe { ptr::read(self.inner[self.idx].as_ptr()) };
             self.idx += 1;
             Some(t)
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len - self.idx;
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for IntoIter<T, N> {
+    fn clone(&self) -> Self {
+        let array: Array<T, N> = self.as_slice().iter().cloned().collect();
+        array.into_iter()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for IntoIter<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
+
+pub struct Drain<'a, T, const N: usize> {
+    array: &'a mut Array<T, N>,
+    start: usize,
+    idx: usize,
+    end: usize,
+    tail: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for Drain<'a, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx == self.end {
+            None
+        } else {
+            let t = unsafe { ptr::read(self.array.as_ptr().add(self.idx)) };
+            self.idx += 1;
+            Some(t)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.idx;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for Drain<'a, T, N> {}
+
+impl<'a, T, const N: usize> Drop for Drain<'a, T, N> {
+    fn drop(&mut self) {
+        for _ in &mut *self {}
+        let tail = self.tail - self.end;
+        unsafe {
+            let p = self.array.as_mut_ptr();
+            ptr::copy(p.add(self.end), p.add(self.start), tail);
+        }
+        self.array.len = self.start + tail;
+    }
 }
 
-impl<T: Copy, const N: usize> Deref for Array<T, N> {
+impl<T, const N: usize> Deref for Array<T, N> {
     type Target = [T];
 
     fn deref(&self) -> &Self::Target {
-        &self.inner[..self.len]
+        self.as_slice()
     }
 }
 
-impl<T: Copy, const N: usize> DerefMut for Array<T, N> {
+impl<T, const N: usize> DerefMut for Array<T, N> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner[..self.len]
+        self.as_mut_slice()
     }
 }
 
-impl<T: Copy, const N: usize> Index<usize> for Array<T, N> {
+impl<T, const N: usize> Index<usize> for Array<T, N> {
     type Output = T;
 
     fn index(&self, idx: usize) -> &Self::Output {
@@ -188,11 +403,11 @@ impl<T: Copy, const N: usize> Index<usize> for Array<T, N> {
             idx,
             self.len
         );
-        &self.inner[idx]
+        &self.as_slice()[idx]
     }
 }
 
-impl<T: Copy, const N: usize> IndexMut<usize> for Array<T, N> {
+impl<T, const N: usize> IndexMut<usize> for Array<T, N> {
     fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
         assert!(
             idx < self.len,
@@ -200,6 +415,192 @@ impl<T: Copy, const N: usize> IndexMut<usize> for Array<T, N> {
             idx,
             self.len
         );
-        &mut self.inner[idx]
+        &mut self.as_mut_slice()[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // borrows its counter so leaked elements own no allocations
+    #[derive(Debug, Clone)]
+    struct D<'a>(usize, &'a Cell<usize>);
+
+    impl Drop for D<'_> {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    fn filled<const N: usize>(drops: &Cell<usize>, n: usize) -> Array<D<'_>, N> {
+        (0..n).map(|i| D(i, drops)).collect()
+    }
+
+    fn values<const N: usize>(array: &Array<D<'_>, N>) -> Vec<usize> {
+        array.iter().map(|d| d.0).collect()
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 4> = Array::new();
+        array.push(D(0, &drops));
+        array.push(D(1, &drops));
+        assert_eq!(values(&array), [0, 1]);
+        assert_eq!(array.pop().map(|d| d.0), Some(1));
+        assert_eq!(drops.get(), 1);
+        drop(array);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 5> = filled(&drops, 3);
+        array.insert(1, D(9, &drops));
+        array.insert(4, D(8, &drops));
+        assert_eq!(values(&array), [0, 9, 1, 2, 8]);
+        assert_eq!(drops.get(), 0);
+        assert_eq!(array.remove(0).0, 0);
+        assert_eq!(array.remove(3).0, 8);
+        assert_eq!(values(&array), [9, 1, 2]);
+        assert_eq!(drops.get(), 2);
+        drop(array);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn swap_remove() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 4> = filled(&drops, 4);
+        assert_eq!(array.swap_remove(1).0, 1);
+        assert_eq!(values(&array), [0, 3, 2]);
+        assert_eq!(array.swap_remove(2).0, 2);
+        assert_eq!(values(&array), [0, 3]);
+        assert_eq!(drops.get(), 2);
+        drop(array);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn truncate() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 5> = filled(&drops, 5);
+        array.truncate(6);
+        assert_eq!(drops.get(), 0);
+        array.truncate(2);
+        assert_eq!(values(&array), [0, 1]);
+        assert_eq!(drops.get(), 3);
+        array.clear();
+        assert!(array.is_empty());
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn retain() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 5> = filled(&drops, 5);
+        array.retain(|d| d.0 % 2 == 0);
+        assert_eq!(values(&array), [0, 2, 4]);
+        assert_eq!(drops.get(), 2);
+        drop(array);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn drain_consumed() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 5> = filled(&drops, 5);
+        let drained: Vec<_> = array.drain(1..3).map(|d| d.0).collect();
+        assert_eq!(drained, [1, 2]);
+        assert_eq!(values(&array), [0, 3, 4]);
+        assert_eq!(drops.get(), 2);
+        drop(array);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn drain_partly_consumed() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 5> = filled(&drops, 5);
+        let mut drain = array.drain(1..4);
+        assert_eq!(drain.next().map(|d| d.0), Some(1));
+        assert_eq!(drain.len(), 2);
+        drop(drain);
+        assert_eq!(values(&array), [0, 4]);
+        assert_eq!(drops.get(), 3);
+        drop(array);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn drain_leaked() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 5> = filled(&drops, 5);
+        mem::forget(array.drain(1..3));
+        assert_eq!(values(&array), [0]);
+        assert_eq!(drops.get(), 0);
+        drop(array);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn into_iter_partly_consumed() {
+        let drops = Cell::new(0);
+        let array: Array<D, 4> = filled(&drops, 4);
+        let mut iter = array.into_iter();
+        assert_eq!(iter.next().map(|d| d.0), Some(0));
+        assert_eq!(iter.as_slice().len(), 3);
+        assert_eq!(iter.len(), 3);
+        let clone = iter.clone();
+        assert_eq!(drops.get(), 1);
+        drop(iter);
+        assert_eq!(drops.get(), 4);
+        assert_eq!(clone.map(|d| d.0).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(drops.get(), 7);
+    }
+
+    #[test]
+    fn reserve_exact() {
+        let drops = Cell::new(0);
+        let array: Array<D, 2> = filled(&drops, 2);
+        let mut array = array.reserve_exact::<4>();
+        assert_eq!(drops.get(), 0);
+        assert_eq!(array.capacity(), 4);
+        array.push(D(2, &drops));
+        assert_eq!(values(&array), [0, 1, 2]);
+        drop(array);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn try_push_full() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 2> = filled(&drops, 1);
+        assert!(array.try_push(D(1, &drops)).is_ok());
+        let rejected = array.try_push(D(2, &drops)).unwrap_err();
+        assert_eq!(rejected.0, 2);
+        assert_eq!(values(&array), [0, 1]);
+        assert_eq!(drops.get(), 0);
+        drop(rejected);
+        assert_eq!(drops.get(), 1);
+        drop(array);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let drops = Cell::new(0);
+        let mut array: Array<D, 4> = filled(&drops, 2);
+        array.extend((2..4).map(|i| D(i, &drops)));
+        assert_eq!(values(&array), [0, 1, 2, 3]);
+        let clone = array.clone();
+        assert_eq!(values(&clone), values(&array));
+        drop(clone);
+        assert_eq!(drops.get(), 4);
+        drop(array);
+        assert_eq!(drops.get(), 8);
     }
 }
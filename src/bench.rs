@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+use bevy::math::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::phys::*;
+
+pub const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+// scatters bodies over an area that grows with their number, so every size
+// has roughly the same density of overlaps
+pub fn bodies(n: usize, seed: u64) -> Vec<RigidBody> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let side = (n as f32).sqrt() * 2.0;
+    (0..n)
+        .map(|_| {
+            let status = if rng.gen_bool(0.8) {
+                Status::Static
+            } else {
+                Status::Dynamic
+            };
+            let position = Vec2::new(rng.gen_range(0.0, side), rng.gen_range(0.0, side));
+            let (width, height) = (rng.gen_range(0.1, 1.5), rng.gen_range(0.1, 1.5));
+            RigidBody::new(status, 1.0, 0.5)
+                .shape(Vec2::new(-width, -height) / 2.0, width, height)
+                .position(position)
+                .rotation(rng.gen_range(0.0, std::f32::consts::PI))
+        })
+        .collect()
+}
+
+fn time<F: FnMut() -> Vec<(usize, usize)>>(mut f: F) -> (Duration, Vec<(usize, usize)>) {
+    let runs = 5;
+    let start = Instant::now();
+    let mut pairs = Vec::new();
+    for _ in 0..runs {
+        pairs = f();
+    }
+    (start.elapsed() / runs, pairs)
+}
+
+pub fn run() {
+    println!(
+        "{:>8} {:>8} {:>14} {:>14}",
+        "bodies", "pairs", "naive", "broad phase"
+    );
+    for &n in &SIZES {
+        let bodies = bodies(n, 47);
+        let (naive, _) = time(|| naive_pairs(&bodies));
+        let (broad, pairs) = time(|| broad_phase(&bodies));
+        println!("{:>8} {:>8} {:>14?} {:>14?}", n, pairs.len(), naive, broad);
+    }
+}
@@ -16,6 +16,7 @@ pub const MOUSE_SPEED: f32 = 0.03;
 pub const BOB_SPEED: f32 = 5.0;
 pub const MAX_SPEED: f32 = 1.5;
 pub const INC_SPEED: f32 = 3.0;
+pub const REACH: f32 = 2.0;
 
 #[derive(Default)]
 pub struct FirstPersonCamera;
@@ -30,6 +31,17 @@ pub struct Character {
     bob_direction: f32,
 }
 
+impl Character {
+    pub fn rotation(&self) -> (f32, f32) {
+        (self.yrot, self.xrot)
+    }
+
+    pub fn set_rotation(&mut self, yrot: f32, xrot: f32) {
+        self.yrot = yrot;
+        self.xrot = xrot;
+    }
+}
+
 impl Default for Character {
     fn default() -> Self {
         Self {
@@ -44,11 +56,6 @@ impl Default for Character {
     }
 }
 
-#[derive(Debug)]
-pub struct Sensor {
-    pub character: Entity,
-}
-
 #[derive(Bundle)]
 pub struct CharBundle {
     pub global_transform: GlobalTransform,
@@ -57,17 +64,9 @@ pub struct CharBundle {
     pub body: RigidBody,
 }
 
-#[derive(Bundle)]
-pub struct SensorBundle {
-    pub global_transform: GlobalTransform,
-    pub transform: Transform,
-    pub controller: Sensor,
-    pub body: RigidBody,
-}
-
 #[derive(Default)]
 pub struct SensorListenerState {
-    reader: EventReader<Manifold>,
+    entered: EventReader<SensorEntered>,
 }
 
 pub fn character_controller_system(
@@ -188,143 +187,87 @@ pub fn sensor_system(
     assets: Res<AssetServer>,
     audio: Res<AudioOutput>,
     input: Res<Input<KeyCode>>,
-    events: Res<Events<Manifold>>,
+    world: Res<PhysicsWorld>,
+    handles: Res<BodyHandles>,
+    entered: Res<Events<SensorEntered>>,
     mut state: ResMut<SensorListenerState>,
     mut current: ResMut<CurrentRoom>,
+    mut changed: ResMut<Events<RoomChanged>>,
     mut active: Query<With<ActiveRoom, Entity>>,
-    mut sensor: Query<&Sensor>,
-    mut connection: Query<Mut<Connection>>,
+    mut connection: Query<(Mut<Connection>, Mut<Joint>)>,
     mut bodies: Query<Mut<RigidBody>>,
     mut room_sensors: Query<&RoomSensor>,
     mut players: Query<(Entity, Mut<Character>)>,
 ) {
-    for manifold in state.reader.iter(&events) {
-        let is_active_controller = if let Ok(controller) = players.get::<Character>(manifold.a) {
+    for event in state.entered.iter(&entered) {
+        if let Ok(controller) = players.get::<Character>(event.other) {
             if !controller.active {
                 continue;
             }
-            true
-        } else if let Ok(controller) = players.get::<Character>(manifold.b) {
-            if !controller.active {
-                continue;
-            }
-            true
         } else {
-            false
-        };
-
-        if is_active_controller {
-            if let Ok(room) = room_sensors.get::<RoomSensor>(manifold.b) {
-                for e in &mut active.iter() {
-                    commands.remove_one::<ActiveRoom>(e);
-                }
-
-                let mut body = bodies.get_mut::<RigidBody>(manifold.b).unwrap();
-                body.set_active(false);
-                mem::drop(body);
-
-                current.entity = room.0;
-                let body = bodies.get::<RigidBody>(room.0).unwrap();
-                let position = body.position;
-                let rotation = body.rotation;
-                mem::drop(body);
-                for (e, mut controller) in &mut players.iter() {
-                    let mut body = bodies.get_mut::<RigidBody>(e).unwrap();
-                    body.position -= position;
-                    let rot = Mat2::from_angle(rotation);
-                    body.position = rot * body.position;
-                    body.velocity = rot * body.velocity;
-                    controller.yrot -= rotation;
-                }
-            } else if let Ok(room) = room_sensors.get::<RoomSensor>(manifold.a) {
-                for e in &mut active.iter() {
-                    commands.remove_one::<ActiveRoom>(e);
-                }
-
-                let mut body = bodies.get_mut::<RigidBody>(manifold.a).unwrap();
-                body.set_active(false);
-                mem::drop(body);
-
-                current.entity = room.0;
-                let body = bodies.get::<RigidBody>(room.0).unwrap();
-                let position = body.position;
-                let rotation = body.rotation;
-                mem::drop(body);
-                for (e, mut controller) in &mut players.iter() {
-                    let mut body = bodies.get_mut::<RigidBody>(e).unwrap();
-                    body.position -= position;
-                    let rot = Mat2::from_angle(rotation);
-                    body.position = rot * body.position;
-                    body.velocity = rot * body.velocity;
-                    controller.yrot -= rotation;
-                }
-            }
+            continue;
         }
 
-        if let Ok(sensor) = sensor.get::<Sensor>(manifold.a) {
-            if let Ok(controller) = players.get::<Character>(sensor.character) {
-                if !controller.active {
-                    continue;
-                }
+        if let Ok(room) = room_sensors.get::<RoomSensor>(event.sensor) {
+            for e in &mut active.iter() {
+                commands.remove_one::<ActiveRoom>(e);
             }
-            if let Ok(mut conn) = connection.get_mut::<Connection>(manifold.b) {
-                if input.just_pressed(KeyCode::Space) {
-                    let mut body = bodies.get_mut::<RigidBody>(manifold.b).unwrap();
-                    let step = assets.load("assets/sound/door.mp3").unwrap();
-                    audio.play(step);
-                    conn.open = !conn.open;
-                    if !conn.open {
-                        body.rotation -= 90.0_f32.to_radians();
-                        let rot = Mat2::from_angle(-body.rotation);
-                        let offset = rot * Vec2::new(0.5, 0.5);
-                        body.position -= offset;
-                        body.set_sensor(false);
-                    } else {
-                        let rot = Mat2::from_angle(-body.rotation);
-                        let offset = rot * Vec2::new(0.5, 0.5);
-                        body.position += offset;
-                        body.rotation += 90.0_f32.to_radians();
-                        body.set_sensor(true);
-                    }
 
-                    mem::drop(body);
-                    bodies
-                        .get_mut::<RigidBody>(conn.sensor)
-                        .unwrap()
-                        .set_active(conn.open);
-                }
-            }
-        } else if let Ok(sensor) = sensor.get::<Sensor>(manifold.b) {
-            if let Ok(controller) = players.get::<Character>(sensor.character) {
-                if !controller.active {
-                    continue;
-                }
-            }
-            if let Ok(mut conn) = connection.get_mut::<Connection>(manifold.a) {
-                if input.just_pressed(KeyCode::Space) {
-                    let mut body = bodies.get_mut::<RigidBody>(manifold.a).unwrap();
-                    conn.open = !conn.open;
-                    if !conn.open {
-                        body.rotation -= 90.0_f32.to_radians();
-                        let rot = Mat2::from_angle(-body.rotation);
-                        let offset = rot * Vec2::new(0.5, 0.5);
-                        body.position -= offset;
-                        body.set_sensor(false);
-                    } else {
-                        let rot = Mat2::from_angle(-body.rotation);
-                        let offset = rot * Vec2::new(0.5, 0.5);
-                        body.position += offset;
-                        body.rotation += 90.0_f32.to_radians();
-                        body.set_sensor(true);
-                    }
+            let mut body = bodies.get_mut::<RigidBody>(event.sensor).unwrap();
+            body.set_active(false);
+            mem::drop(body);
 
-                    mem::drop(body);
-                    bodies
-                        .get_mut::<RigidBody>(conn.sensor)
-                        .unwrap()
-                        .set_active(conn.open);
-                }
+            current.entity = room.0;
+            changed.send(RoomChanged(room.0));
+            let body = bodies.get::<RigidBody>(room.0).unwrap();
+            let position = body.position;
+            let rotation = body.rotation;
+            mem::drop(body);
+            for (e, mut controller) in &mut players.iter() {
+                let mut body = bodies.get_mut::<RigidBody>(e).unwrap();
+                body.position -= position;
+                let rot = Mat2::from_angle(rotation);
+                body.position = rot * body.position;
+                body.velocity = rot * body.velocity;
+                controller.yrot -= rotation;
             }
         }
     }
+
+    if !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    let mut doors = Vec::new();
+    for (e, controller) in &mut players.iter() {
+        if !controller.active {
+            continue;
+        }
+        // walls and props are in the mask so doors behind them can't be used
+        let origin = bodies.get::<RigidBody>(e).unwrap().position;
+        let facing = Mat2::from_angle(-controller.yrot) * Vec2::new(0.0, -1.0);
+        let hit = world.raycast(origin, facing, REACH, LAYER_DOOR | LAYER_DEFAULT);
+        if let Some(door) = hit.and_then(|hit| handles.entity(hit.body)) {
+            doors.push(door);
+        }
+    }
+    for door in doors {
+        let (mut conn, mut joint) = match (
+            connection.get_mut::<Connection>(door),
+            connection.get_mut::<Joint>(door),
+        ) {
+            (Ok(conn), Ok(joint)) => (conn, joint),
+            _ => continue,
+        };
+        let mut body = bodies.get_mut::<RigidBody>(door).unwrap();
+        let step = assets.load("assets/sound/door.mp3").unwrap();
+        audio.play(step);
+        let open = !conn.open;
+        conn.set_open(&mut body, &mut joint, open);
+
+        mem::drop(body);
+        bodies
+            .get_mut::<RigidBody>(conn.sensor)
+            .unwrap()
+            .set_active(conn.open);
+    }
 }
@@ -0,0 +1,242 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::level;
+
+pub const USAGE: &str = "\
+usage: ldjam-47 [options]
+
+options:
+    --level <file>    play the level in <file> (default: assets/levels/default.ron)
+    --generate        play a procedurally generated level
+    --seed <n>        seed for --generate (default: random)
+    --size <n>        number of rooms for --generate (default: 10)
+    --faux            show the faux street scene
+    --start <room>    start in the room with id <room>
+    --new-game        ignore the saved game and start from the beginning
+    --round-player    give the player a round collider that slides around corners
+    --physics-hz <n>  physics steps per second (default: 60)
+    --dot             print the level graph in Graphviz DOT format and exit
+    --map             print the level graph as text and exit
+    --bench           time the physics broad phase against the naive loop and exit
+    --help            print this message and exit";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    Level(PathBuf),
+    Generate { seed: u64, size: usize },
+    Faux,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dump {
+    Dot,
+    Map,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub mode: Mode,
+    pub start: Option<String>,
+    pub dump: Option<Dump>,
+    pub new_game: bool,
+    pub round_player: bool,
+    pub bench: bool,
+    pub physics_hz: f32,
+    pub help: bool,
+}
+
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut level = None;
+    let mut generate = false;
+    let mut seed = None;
+    let mut size = None;
+    let mut faux = false;
+    let mut start = None;
+    let mut dump = None;
+    let mut new_game = false;
+    let mut round_player = false;
+    let mut bench = false;
+    let mut physics_hz = 60.0;
+    let mut help = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--level" => level = Some(PathBuf::from(value("--level")?)),
+            "--generate" => generate = true,
+            "--seed" => {
+                let n = value("--seed")?;
+                seed = Some(n.parse().map_err(|_| format!("invalid seed {:?}", n))?);
+            }
+            "--size" => {
+                let n = value("--size")?;
+                size = Some(n.parse().map_err(|_| format!("invalid size {:?}", n))?);
+            }
+            "--faux" => faux = true,
+            "--start" => start = Some(value("--start")?),
+            "--dot" => dump = Some(Dump::Dot),
+            "--map" => dump = Some(Dump::Map),
+            "--new-game" => new_game = true,
+            "--round-player" => round_player = true,
+            "--bench" => bench = true,
+            "--physics-hz" => {
+                let n = value("--physics-hz")?;
+                physics_hz = n
+                    .parse()
+                    .ok()
+                    .filter(|&hz: &f32| hz > 0.0 && hz.is_finite())
+                    .ok_or_else(|| format!("invalid physics rate {:?}", n))?;
+            }
+            "--help" | "-h" => help = true,
+            _ => return Err(format!("unknown argument {:?}", arg)),
+        }
+    }
+
+    if [level.is_some(), generate, faux]
+        .iter()
+        .filter(|&&set| set)
+        .count()
+        > 1
+    {
+        return Err("--level, --generate and --faux are mutually exclusive".to_string());
+    }
+    if !generate && (seed.is_some() || size.is_some()) {
+        return Err("--seed and --size only work with --generate".to_string());
+    }
+    if generate && dump.is_some() {
+        return Err("--dot and --map only work with level files".to_string());
+    }
+    if faux && (start.is_some() || dump.is_some()) {
+        return Err("--faux has no level to start in or print".to_string());
+    }
+
+    let mode = if faux {
+        Mode::Faux
+    } else if generate {
+        let size = size.unwrap_or(10);
+        if size == 0 {
+            return Err("--size must be at least 1".to_string());
+        }
+        Mode::Generate {
+            seed: seed.unwrap_or_else(random_seed),
+            size,
+        }
+    } else {
+        Mode::Level(level.unwrap_or_else(|| PathBuf::from(level::DEFAULT)))
+    };
+
+    Ok(Options {
+        mode,
+        start,
+        dump,
+        new_game,
+        round_player,
+        bench,
+        physics_hz,
+        help,
+    })
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<Options, String> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse_str("").unwrap();
+        assert_eq!(options.mode, Mode::Level(PathBuf::from(level::DEFAULT)));
+        assert_eq!(options.start, None);
+        assert_eq!(options.dump, None);
+        assert_eq!(options.physics_hz, 60.0);
+        assert!(!options.new_game && !options.round_player && !options.bench && !options.help);
+    }
+
+    #[test]
+    fn modes_are_mutually_exclusive() {
+        for args in &[
+            "--level a.ron --generate",
+            "--level a.ron --faux",
+            "--generate --faux",
+            "--faux --level a.ron --generate",
+        ] {
+            assert_eq!(
+                parse_str(args),
+                Err("--level, --generate and --faux are mutually exclusive".to_string()),
+                "{}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn options_that_need_a_mode() {
+        assert!(parse_str("--seed 1").is_err());
+        assert!(parse_str("--level a.ron --size 3").is_err());
+        assert!(parse_str("--generate --dot").is_err());
+        assert!(parse_str("--generate --map").is_err());
+        assert!(parse_str("--faux --start bedroom_a").is_err());
+        assert!(parse_str("--faux --map").is_err());
+        assert!(parse_str("--level a.ron --dot --start corridor").is_ok());
+    }
+
+    #[test]
+    fn seed_and_size() {
+        let options = parse_str("--generate --seed 42 --size 5").unwrap();
+        assert_eq!(options.mode, Mode::Generate { seed: 42, size: 5 });
+        match parse_str("--generate --seed 7").unwrap().mode {
+            Mode::Generate { seed, size } => assert_eq!((seed, size), (7, 10)),
+            mode => panic!("unexpected mode {:?}", mode),
+        }
+
+        assert_eq!(
+            parse_str("--generate --seed abc"),
+            Err("invalid seed \"abc\"".to_string())
+        );
+        assert_eq!(
+            parse_str("--generate --size -1"),
+            Err("invalid size \"-1\"".to_string())
+        );
+        assert_eq!(
+            parse_str("--generate --size 0"),
+            Err("--size must be at least 1".to_string())
+        );
+        assert_eq!(
+            parse_str("--generate --seed"),
+            Err("--seed needs a value".to_string())
+        );
+    }
+
+    #[test]
+    fn physics_hz() {
+        assert_eq!(parse_str("--physics-hz 120").unwrap().physics_hz, 120.0);
+        assert_eq!(parse_str("--physics-hz 29.5").unwrap().physics_hz, 29.5);
+        for hz in &["0", "-60", "inf", "NaN", "fast"] {
+            assert_eq!(
+                parse_str(&format!("--physics-hz {}", hz)),
+                Err(format!("invalid physics rate {:?}", hz)),
+            );
+        }
+        assert!(parse_str("--physics-hz").is_err());
+    }
+
+    #[test]
+    fn unknown_argument() {
+        assert_eq!(
+            parse_str("--level a.ron --fly"),
+            Err("unknown argument \"--fly\"".to_string())
+        );
+    }
+}
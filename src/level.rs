@@ -1,494 +1,711 @@
-use bevy::math::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
 use bevy::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use serde::Deserialize;
 
+use crate::phys::Aabb;
 use crate::proc::*;
-use crate::room::*;
-
-pub fn new() -> LevelPrototype {
-    let r1a = RoomPrototype {
-        name: "Bedroom".to_string(),
-        description: "I should get some soy milk.".to_string(),
-        color: Color::rgb(1.0, 1.0, 1.0),
-        width: 12.0,
-        height: 2.0,
-        depth: 8.0,
-        doors: vec![Door::East].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 1,
-            from: Door::East,
-            to: Door::West,
-        }],
-        props: vec![
-            PropPrototype {
-                name: "bed".to_string(),
-                position: Vec2::new(4.5, -2.5),
-                rotation: 0.0,
-            },
-            PropPrototype {
-                name: "desk".to_string(),
-                position: Vec2::new(-1.5, -3.5),
-                rotation: 270.0_f32.to_radians(),
-            },
-            PropPrototype {
-                name: "chair".to_string(),
-                position: Vec2::new(-1.3, -2.4),
-                rotation: 0.0,
-            },
-        ],
-    };
-    let r2 = RoomPrototype {
-        name: "Corridor".to_string(),
-        description: "Shoes, keys, wallet, phone, got everything.".to_string(),
-        color: Color::rgb(1.0, 1.0, 1.0),
-        width: 2.0,
-        height: 2.0,
-        depth: 8.0,
-        doors: vec![Door::North].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 2,
-            from: Door::North,
-            to: Door::West,
-        }],
-        props: vec![],
-    };
-    let r1b = RoomPrototype {
-        name: r1a.name.clone(),
-        description: "I should get some soy milk.".to_string(),
-        color: Color::rgb(1.0, 1.0, 1.0),
-        width: r1a.width,
-        height: r1a.height,
-        depth: r1a.depth,
-        doors: vec![Door::East].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 3,
-            from: Door::East,
-            to: Door::West,
-        }],
-        props: r1a.props.clone(),
-    };
-    let r3a = RoomPrototype {
-        name: "Abjection".to_string(),
-        description: "Shoes, keys... This isn't my corridor.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
+
+pub const DEFAULT: &str = "assets/levels/default.ron";
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ron::Error),
+    DuplicateId(String),
+    UnknownTemplate { room: String, template: String },
+    TemplateCycle(String),
+    MissingField { room: String, field: &'static str },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "could not read level file: {}", err),
+            LoadError::Parse(err) => write!(f, "could not parse level file: {}", err),
+            LoadError::DuplicateId(id) => write!(f, "room id {:?} is used more than once", id),
+            LoadError::UnknownTemplate { room, template } => write!(
+                f,
+                "room {:?} uses template {:?}, which does not exist",
+                room, template
+            ),
+            LoadError::TemplateCycle(room) => {
+                write!(f, "templates of room {:?} form a cycle", room)
+            }
+            LoadError::MissingField { room, field } => write!(
+                f,
+                "room {:?} has no {} and none of its templates set one",
+                room, field
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ron::Error> for LoadError {
+    fn from(err: ron::Error) -> Self {
+        LoadError::Parse(err)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoomDefinition {
+    pub id: String,
+    pub template: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub color: Option<Color>,
+    pub width: Option<f32>,
+    pub depth: Option<f32>,
+    pub height: Option<f32>,
+    pub doors: Option<HashSet<Door>>,
+    pub edges: Option<Vec<EdgePrototype>>,
+    pub props: Option<Vec<PropPrototype>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelDefinition {
+    pub start: String,
+    pub goal: Option<String>,
+    pub rooms: Vec<RoomDefinition>,
+}
+
+impl LevelDefinition {
+    pub fn resolve(&self) -> Result<LevelPrototype, LoadError> {
+        let mut ids = HashMap::new();
+        for room in &self.rooms {
+            if ids.insert(room.id.as_str(), room).is_some() {
+                return Err(LoadError::DuplicateId(room.id.clone()));
+            }
+        }
+
+        let mut rooms = Vec::with_capacity(self.rooms.len());
+        for room in &self.rooms {
+            let mut chain = vec![room];
+            let mut current = room;
+            while let Some(template) = &current.template {
+                current = match ids.get(template.as_str()) {
+                    Some(&next) => next,
+                    None => {
+                        return Err(LoadError::UnknownTemplate {
+                            room: current.id.clone(),
+                            template: template.clone(),
+                        })
+                    }
+                };
+                if chain.iter().any(|r| r.id == current.id) {
+                    return Err(LoadError::TemplateCycle(room.id.clone()));
+                }
+                chain.push(current);
+            }
+
+            rooms.push(RoomPrototype {
+                id: room.id.clone(),
+                name: required(&chain, "name", |r| r.name.as_ref())?,
+                description: required(&chain, "description", |r| r.description.as_ref())?,
+                color: required(&chain, "color", |r| r.color.as_ref())?,
+                width: required(&chain, "width", |r| r.width.as_ref())?,
+                depth: required(&chain, "depth", |r| r.depth.as_ref())?,
+                height: required(&chain, "height", |r| r.height.as_ref())?,
+                doors: inherited(&chain, |r| r.doors.as_ref()),
+                edges: inherited(&chain, |r| r.edges.as_ref()),
+                props: inherited(&chain, |r| r.props.as_ref()),
+            });
+        }
+
+        Ok(LevelPrototype {
+            start: self.start.clone(),
+            goal: self.goal.clone(),
+            rooms,
+        })
+    }
+}
+
+fn inherited<'a, T, F>(chain: &[&'a RoomDefinition], field: F) -> T
+where
+    T: Clone + Default + 'a,
+    F: Fn(&'a RoomDefinition) -> Option<&'a T>,
+{
+    chain
+        .iter()
+        .find_map(|&r| field(r))
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn required<'a, T, F>(
+    chain: &[&'a RoomDefinition],
+    name: &'static str,
+    field: F,
+) -> Result<T, LoadError>
+where
+    T: Clone + 'a,
+    F: Fn(&'a RoomDefinition) -> Option<&'a T>,
+{
+    chain
+        .iter()
+        .find_map(|&r| field(r))
+        .cloned()
+        .ok_or_else(|| LoadError::MissingField {
+            room: chain[0].id.clone(),
+            field: name,
+        })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelError {
+    InvalidStart(String),
+    InvalidGoal(String),
+    DanglingEdge {
+        room: String,
+        door: Door,
+        target: String,
+    },
+    EdgeWithoutDoor {
+        room: String,
+        door: Door,
+    },
+    DoorWithoutEdge {
+        room: String,
+        door: Door,
+    },
+    MissingTargetDoor {
+        room: String,
+        door: Door,
+        target: String,
+        to: Door,
+    },
+    OneWayEdge {
+        room: String,
+        door: Door,
+        target: String,
+        to: Door,
+    },
+    UnreachableRoom(String),
+    PropOutOfBounds {
+        room: String,
+        prop: String,
+    },
+}
+
+impl LevelError {
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            LevelError::InvalidStart(_)
+            | LevelError::InvalidGoal(_)
+            | LevelError::DanglingEdge { .. }
+            | LevelError::EdgeWithoutDoor { .. }
+            | LevelError::DoorWithoutEdge { .. } => true,
+            LevelError::MissingTargetDoor { .. }
+            | LevelError::OneWayEdge { .. }
+            | LevelError::UnreachableRoom(_)
+            | LevelError::PropOutOfBounds { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::InvalidStart(id) => write!(f, "start room {:?} does not exist", id),
+            LevelError::InvalidGoal(id) => write!(f, "goal room {:?} does not exist", id),
+            LevelError::DanglingEdge { room, door, target } => write!(
+                f,
+                "{:?} door of room {:?} leads to {:?}, which does not exist",
+                door, room, target
+            ),
+            LevelError::EdgeWithoutDoor { room, door } => write!(
+                f,
+                "room {:?} has an edge from its {:?} door, but no such door",
+                room, door
+            ),
+            LevelError::DoorWithoutEdge { room, door } => {
+                write!(f, "{:?} door of room {:?} leads nowhere", door, room)
+            }
+            LevelError::MissingTargetDoor {
+                room,
+                door,
+                target,
+                to,
+            } => write!(
+                f,
+                "{:?} door of room {:?} enters {:?} through its {:?} wall, which has no door",
+                door, room, target, to
+            ),
+            LevelError::OneWayEdge {
+                room,
+                door,
+                target,
+                to,
+            } => write!(
+                f,
+                "{:?} door of room {:?} enters {:?} through its {:?} door, which does not lead back",
+                door, room, target, to
+            ),
+            LevelError::UnreachableRoom(id) => {
+                write!(f, "room {:?} can not be reached from the start", id)
+            }
+            LevelError::PropOutOfBounds { room, prop } => {
+                write!(f, "prop {:?} is outside of room {:?}", prop, room)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+pub fn validate(level: &LevelPrototype) -> Result<(), Vec<LevelError>> {
+    let mut errors = Vec::new();
+
+    let start = level.index(&level.start);
+    if start.is_none() {
+        errors.push(LevelError::InvalidStart(level.start.clone()));
+    }
+    if let Some(goal) = &level.goal {
+        if level.index(goal).is_none() {
+            errors.push(LevelError::InvalidGoal(goal.clone()));
+        }
+    }
+
+    for room in &level.rooms {
+        for &door in &room.doors {
+            if !room.edges.iter().any(|edge| edge.from == door) {
+                errors.push(LevelError::DoorWithoutEdge {
+                    room: room.id.clone(),
+                    door,
+                });
+            }
+        }
+
+        for edge in &room.edges {
+            if !room.doors.contains(&edge.from) {
+                errors.push(LevelError::EdgeWithoutDoor {
+                    room: room.id.clone(),
+                    door: edge.from,
+                });
+            }
+
+            let target = match level.index(&edge.room) {
+                Some(j) => &level.rooms[j],
+                None => {
+                    errors.push(LevelError::DanglingEdge {
+                        room: room.id.clone(),
+                        door: edge.from,
+                        target: edge.room.clone(),
+                    });
+                    continue;
+                }
+            };
+
+            if !target.doors.contains(&edge.to) {
+                errors.push(LevelError::MissingTargetDoor {
+                    room: room.id.clone(),
+                    door: edge.from,
+                    target: target.id.clone(),
+                    to: edge.to,
+                });
+            } else if !target
+                .edges
+                .iter()
+                .any(|back| back.from == edge.to && back.room == room.id && back.to == edge.from)
+            {
+                errors.push(LevelError::OneWayEdge {
+                    room: room.id.clone(),
+                    door: edge.from,
+                    target: target.id.clone(),
+                    to: edge.to,
+                });
+            }
+        }
+    }
+
+    if let Some(start) = start {
+        let mut visited = vec![false; level.rooms.len()];
+        let mut queue = VecDeque::new();
+        visited[start] = true;
+        queue.push_back(start);
+        while let Some(i) = queue.pop_front() {
+            for edge in &level.rooms[i].edges {
+                if let Some(j) = level.index(&edge.room) {
+                    if !visited[j] {
+                        visited[j] = true;
+                        queue.push_back(j);
+                    }
+                }
+            }
+        }
+        for (room, &visited) in level.rooms.iter().zip(&visited) {
+            if !visited {
+                errors.push(LevelError::UnreachableRoom(room.id.clone()));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// props are checked apart from the graph because their footprints come from
+// their meshes, props without a footprint are checked by their center
+pub fn validate_props(
+    level: &LevelPrototype,
+    footprints: &HashMap<String, Aabb>,
+) -> Result<(), Vec<LevelError>> {
+    let mut errors = Vec::new();
+    for room in &level.rooms {
+        let (w, d) = (room.width / 2.0, room.depth / 2.0);
+        let bounds = Aabb::new(Vec2::new(-w, -d), Vec2::new(w, d));
+        for prop in &room.props {
+            let aabb = match footprints.get(&prop.name) {
+                Some(footprint) => footprint.transform(prop.position, prop.rotation),
+                None => Aabb::new(prop.position, prop.position),
+            };
+            if !bounds.contains(&aabb) {
+                errors.push(LevelError::PropOutOfBounds {
+                    room: room.id.clone(),
+                    prop: prop.name.clone(),
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+pub fn check_props(level: &LevelPrototype, footprints: &HashMap<String, Aabb>) {
+    if let Err(errors) = validate_props(level, footprints) {
+        if cfg!(debug_assertions) {
+            for error in &errors {
+                eprintln!("warning: {}", error);
+            }
+        }
+    }
+}
+
+pub fn check(level: &LevelPrototype) -> bool {
+    match validate(level) {
+        Ok(()) => true,
+        Err(errors) => {
+            for error in &errors {
+                if error.is_fatal() {
+                    eprintln!("error: {}", error);
+                } else if cfg!(debug_assertions) {
+                    eprintln!("warning: {}", error);
+                }
+            }
+            !errors.iter().any(LevelError::is_fatal)
+        }
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<LevelPrototype, LoadError> {
+    let file = File::open(path)?;
+    let definition: LevelDefinition = ron::de::from_reader(file)?;
+    definition.resolve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(id: &str, doors: &[Door], edges: &[(&str, Door, Door)]) -> RoomPrototype {
+        RoomPrototype {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            color: Color::WHITE,
+            width: 4.0,
+            depth: 4.0,
+            height: 2.0,
+            doors: doors.iter().copied().collect(),
+            edges: edges
+                .iter()
+                .map(|&(room, from, to)| EdgePrototype {
+                    room: room.to_string(),
+                    from,
+                    to,
+                })
+                .collect(),
+            props: Vec::new(),
+        }
+    }
+
+    fn level(start: &str, rooms: Vec<RoomPrototype>) -> LevelPrototype {
+        LevelPrototype {
+            start: start.to_string(),
+            goal: None,
+            rooms,
+        }
+    }
+
+    fn errors(level: &LevelPrototype) -> Vec<LevelError> {
+        validate(level).err().unwrap_or_default()
+    }
+
+    // two rooms connected through a's north and b's south door
+    fn pair() -> LevelPrototype {
+        level(
+            "a",
+            vec![
+                room("a", &[Door::North], &[("b", Door::North, Door::South)]),
+                room("b", &[Door::South], &[("a", Door::South, Door::North)]),
+            ],
+        )
+    }
+
+    fn resolve(rooms: &str) -> Result<LevelPrototype, LoadError> {
+        let source = format!(
+            "#![enable(implicit_some)]\n(start: \"a\", rooms: [{}])",
+            rooms
+        );
+        ron::de::from_str::<LevelDefinition>(&source)
+            .unwrap()
+            .resolve()
+    }
+
+    const BASE: &str = r#"(
+        id: "a",
+        name: "Base",
+        description: "The first room.",
+        color: (r: 1.0, g: 1.0, b: 1.0, a: 1.0),
         width: 4.0,
         height: 2.0,
-        depth: 4.0,
-        doors: vec![Door::East, Door::South].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 4,
-                from: Door::East,
-                to: Door::North,
-            },
-            EdgePrototype {
-                index: 5,
-                from: Door::South,
-                to: Door::North,
-            },
-        ],
-        props: vec![PropPrototype {
-            name: "lamp".to_string(),
-            position: Vec2::new(-1.8, -1.8),
-            rotation: 0.0,
-        }],
-    };
-    let r3b = RoomPrototype {
-        name: r3a.name.clone(),
-        description: "This still isn't my corridor.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r3a.width,
-        height: r3a.height,
-        depth: r3a.depth,
-        doors: vec![Door::East].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 3,
-            from: Door::East,
-            to: Door::North,
-        }],
-        props: r3a.props.clone(),
-    };
-    let r4a = RoomPrototype {
-        name: "Gloom".to_string(),
-        description: "Huh, nice flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: 8.0,
-        height: 2.0,
-        depth: 8.0,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 3,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 6,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: vec![PropPrototype {
-            name: "flower_table".to_string(),
-            position: Vec2::zero(),
-            rotation: 0.0,
-        }],
-    };
-    let r4b = RoomPrototype {
-        name: r4a.name.clone(),
-        description: "Huh, lovely flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r4a.width,
-        height: r4a.height,
-        depth: r4a.depth,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 3,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 7,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: r4a.props.clone(),
-    };
-    let r4c = RoomPrototype {
-        name: r4a.name.clone(),
-        description: "Huh, pretty flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r4a.width,
-        height: r4a.height,
-        depth: r4a.depth,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 3,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 8,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: r4a.props.clone(),
-    };
-    let r4d = RoomPrototype {
-        name: r4a.name.clone(),
-        description: "Huh, flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r4a.width,
-        height: r4a.height,
-        depth: r4a.depth,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 12,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 9,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: r4a.props.clone(),
-    };
-    let r4e = RoomPrototype {
-        name: r4a.name.clone(),
-        description: "Huh, ugly flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r4a.width,
-        height: r4a.height,
-        depth: r4a.depth,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 3,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 10,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: r4a.props.clone(),
-    };
-    let r4f = RoomPrototype {
-        name: r4a.name.clone(),
-        description: "Huh, terrible flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r4a.width,
-        height: r4a.height,
-        depth: r4a.depth,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 3,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 11,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: r4a.props.clone(),
-    };
-    let r4g = RoomPrototype {
-        name: r4a.name.clone(),
-        description: "Huh, florpy flower.".to_string(),
-        color: Color::rgb(0.9, 1.0, 1.0),
-        width: r4a.width,
-        height: r4a.height,
-        depth: r4a.depth,
-        doors: vec![Door::East, Door::West].into_iter().collect(),
-        edges: vec![
-            EdgePrototype {
-                index: 3,
-                from: Door::East,
-                to: Door::West,
-            },
-            EdgePrototype {
-                index: 5,
-                from: Door::West,
-                to: Door::North,
-            },
-        ],
-        props: r4a.props.clone(),
-    };
-    let r5a = RoomPrototype {
-        name: "Betterment".to_string(),
-        description: "Now where was I? Soy milk.".to_string(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: 32.0,
-        height: 2.0,
-        depth: 3.0,
-        doors: vec![Door::East].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 13,
-            from: Door::East,
-            to: Door::North,
-        }],
-        props: vec![],
-    };
-    let r6 = RoomPrototype {
-        name: "Turning".to_string(),
-        description: "Is this the store?.".to_string(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: 6.0,
-        height: 2.0,
-        depth: 6.0,
-        doors: vec![Door::West].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 14,
-            from: Door::West,
-            to: Door::East,
-        }],
-        props: vec![PropPrototype {
-            name: "a".to_string(),
-            position: Vec2::new(0.0, 0.0),
-            rotation: 0.0,
-        }],
-    };
-    let r7 = RoomPrototype {
-        name: "Turning".to_string(),
-        description: "Is this the store?.".to_string(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: 6.0,
-        height: 2.0,
-        depth: 6.0,
-        doors: vec![Door::North].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 15,
-            from: Door::North,
-            to: Door::South,
-        }],
-        props: vec![PropPrototype {
-            name: "b".to_string(),
-            position: Vec2::new(0.0, 0.0),
-            rotation: 0.0,
-        }],
-    };
-    let r8 = RoomPrototype {
-        name: "Turning".to_string(),
-        description: "Is this the store?.".to_string(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: 6.0,
-        height: 2.0,
         depth: 6.0,
-        doors: vec![Door::East].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 16,
-            from: Door::East,
+        doors: [North],
+        edges: [(room: "b", from: North, to: South)],
+    ),"#;
+
+    #[test]
+    fn valid_level() {
+        assert_eq!(validate(&pair()), Ok(()));
+    }
+
+    #[test]
+    fn invalid_start() {
+        let mut level = pair();
+        level.start = "c".to_string();
+        assert!(errors(&level).contains(&LevelError::InvalidStart("c".to_string())));
+    }
+
+    #[test]
+    fn invalid_goal() {
+        let mut level = pair();
+        level.goal = Some("c".to_string());
+        assert_eq!(
+            errors(&level),
+            vec![LevelError::InvalidGoal("c".to_string())]
+        );
+    }
+
+    #[test]
+    fn dangling_edge() {
+        let mut level = pair();
+        level.rooms[1].edges[0].room = "c".to_string();
+        assert!(errors(&level).contains(&LevelError::DanglingEdge {
+            room: "b".to_string(),
+            door: Door::South,
+            target: "c".to_string(),
+        }));
+    }
+
+    #[test]
+    fn edge_without_door() {
+        let mut level = pair();
+        level.rooms[0].doors.clear();
+        assert!(errors(&level).contains(&LevelError::EdgeWithoutDoor {
+            room: "a".to_string(),
+            door: Door::North,
+        }));
+    }
+
+    #[test]
+    fn door_without_edge() {
+        let mut level = pair();
+        level.rooms[0].doors.insert(Door::East);
+        assert_eq!(
+            errors(&level),
+            vec![LevelError::DoorWithoutEdge {
+                room: "a".to_string(),
+                door: Door::East,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_target_door() {
+        let mut level = pair();
+        level.rooms[0].edges[0].to = Door::West;
+        assert!(errors(&level).contains(&LevelError::MissingTargetDoor {
+            room: "a".to_string(),
+            door: Door::North,
+            target: "b".to_string(),
             to: Door::West,
-        }],
-        props: vec![PropPrototype {
-            name: "c".to_string(),
-            position: Vec2::new(0.0, 0.0),
-            rotation: 0.0,
-        }],
-    };
-    let r9 = RoomPrototype {
-        name: "Turning".to_string(),
-        description: "Is this the store?.".to_string(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: 6.0,
-        height: 2.0,
-        depth: 6.0,
-        doors: vec![Door::South].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 17,
-            from: Door::South,
-            to: Door::North,
-        }],
-        props: vec![],
-    };
-    let r10 = RoomPrototype {
-        name: "Turning".to_string(),
-        description: "Is this the store?.".to_string(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: 6.0,
-        height: 2.0,
-        depth: 6.0,
-        doors: vec![Door::West].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 18,
-            from: Door::West,
-            to: Door::East,
-        }],
-        props: vec![PropPrototype {
-            name: "e".to_string(),
-            position: Vec2::new(0.0, 0.0),
-            rotation: 0.0,
-        }],
-    };
-    let r5b = RoomPrototype {
-        name: r5a.name.clone(),
-        description: r5a.description.clone(),
-        color: Color::rgb(0.775, 1.0, 1.0),
-        width: r5a.width,
-        height: r5a.height,
-        depth: r5a.depth,
-        doors: vec![Door::West].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 19,
-            from: Door::West,
-            to: Door::North,
-        }],
-        props: r5a.props.clone(),
-    };
-    let r11 = RoomPrototype {
-        name: "Mistakes".to_string(),
-        description: "I keep making the same mistakes.".to_string(),
-        color: Color::rgb(0.44, 1.0, 1.0),
-        width: 12.0,
-        height: 2.0,
-        depth: 12.0,
-        doors: vec![Door::South].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 20,
-            from: Door::South,
-            to: Door::East,
-        }],
-        props: vec![PropPrototype {
-            name: "rev_chair".to_string(),
-            position: Vec2::zero(),
-            rotation: 0.0,
-        }],
-    };
-    let r90a = RoomPrototype {
-        name: "Repetition".to_string(),
-        description: "Every time.".to_string(),
-        color: Color::rgb(0.44, 1.0, 1.0),
-        width: 12.0,
-        height: 2.0,
-        depth: 8.0,
-        doors: vec![Door::West].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 21,
-            from: Door::West,
-            to: Door::North,
-        }],
-        props: vec![PropPrototype {
-            name: "mobius".to_string(),
-            position: Vec2::new(0.0, 0.0),
-            rotation: 0.0,
-        }],
-    };
-    let r12 = RoomPrototype {
-        name: "Thoughts".to_string(),
-        description: "But it doesn't matter.".to_string(),
-        color: Color::rgb(0.44, 1.0, 1.0),
-        width: 10.0,
-        height: 2.0,
-        depth: 10.0,
-        doors: vec![Door::South].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 22,
-            from: Door::South,
-            to: Door::North,
-        }],
-        props: vec![],
-    };
-    let r13 = RoomPrototype {
-        name: "Memories".to_string(),
-        description: "Because it gets better.".to_string(),
-        color: Color::rgb(0.44, 1.0, 1.0),
-        width: 10.0,
-        height: 2.0,
-        depth: 10.0,
-        doors: vec![Door::South].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 23,
-            from: Door::South,
-            to: Door::North,
-        }],
-        props: vec![PropPrototype {
-            name: "room".to_string(),
-            position: Vec2::zero(),
+        }));
+    }
+
+    #[test]
+    fn one_way_edge() {
+        let mut level = pair();
+        level.rooms[1].edges[0].to = Door::East;
+        level.rooms[0].doors.insert(Door::East);
+        level.rooms[0].edges.push(EdgePrototype {
+            room: "b".to_string(),
+            from: Door::East,
+            to: Door::South,
+        });
+        assert!(errors(&level).contains(&LevelError::OneWayEdge {
+            room: "a".to_string(),
+            door: Door::North,
+            target: "b".to_string(),
+            to: Door::South,
+        }));
+    }
+
+    #[test]
+    fn unreachable_room() {
+        let mut level = pair();
+        level.rooms.push(room("c", &[], &[]));
+        assert_eq!(
+            errors(&level),
+            vec![LevelError::UnreachableRoom("c".to_string())]
+        );
+    }
+
+    #[test]
+    fn prop_out_of_bounds() {
+        let mut level = pair();
+        // centered 0.1 inside the east wall, but two meters wide
+        level.rooms[0].props.push(PropPrototype {
+            name: "bed".to_string(),
+            position: Vec2::new(1.9, 0.0),
             rotation: 0.0,
-        }],
-    };
-    let r90b = RoomPrototype {
-        name: r90a.name.clone(),
-        description: r90a.description.clone(),
-        color: Color::rgb(0.44, 1.0, 1.0),
-        width: r90a.width,
-        height: r90a.height,
-        depth: r90a.depth,
-        doors: vec![Door::South].into_iter().collect(),
-        edges: vec![EdgePrototype {
-            index: 24,
-            from: Door::South,
-            to: Door::East,
-        }],
-        props: r90a.props.clone(),
-    };
-    let r14 = RoomPrototype {
-        name: "Content".to_string(),
-        description: "I am not happy, but I am content.".to_string(),
-        color: Color::rgb(0.0, 1.0, 1.0),
-        width: r1a.width,
-        height: r1a.height,
-        depth: r1a.depth,
-        doors: vec![].into_iter().collect(),
-        edges: vec![],
-        props: r1a.props.clone(),
-    };
-    LevelPrototype {
-        start: 0,
-        rooms: vec![
-            r1a, r2, r1b, r3a, r3b, r4a, r4b, r4c, r4d, r4e, r4f, r4g, r5a, r6, r7, r8, r9, r10,
-            r5b, r11, r90a, r12, r13, r90b, r14,
-        ],
+            status: Default::default(),
+            mass: 1.0,
+            material: None,
+        });
+        assert_eq!(validate_props(&level, &HashMap::new()), Ok(()));
+
+        let mut footprints = HashMap::new();
+        footprints.insert(
+            "bed".to_string(),
+            Aabb::new(Vec2::new(-1.0, -0.5), Vec2::new(1.0, 0.5)),
+        );
+        assert_eq!(
+            validate_props(&level, &footprints),
+            Err(vec![LevelError::PropOutOfBounds {
+                room: "a".to_string(),
+                prop: "bed".to_string(),
+            }])
+        );
+
+        level.rooms[0].props[0].position = Vec2::new(0.9, 0.0);
+        assert_eq!(validate_props(&level, &footprints), Ok(()));
+    }
+
+    #[test]
+    fn default_level() {
+        let level = load(DEFAULT).unwrap();
+        assert!(!errors(&level).iter().any(LevelError::is_fatal));
+    }
+
+    #[test]
+    fn template_inherits_and_overrides() {
+        let level = resolve(&format!(
+            r#"{}
+            (id: "b", template: "a", description: "Again.", doors: [South]),
+            (id: "c", template: "b", width: 8.0),"#,
+            BASE
+        ))
+        .unwrap();
+        let (a, b, c) = (&level.rooms[0], &level.rooms[1], &level.rooms[2]);
+
+        assert_eq!(b.id, "b");
+        assert_eq!(b.name, "Base");
+        assert_eq!(b.description, "Again.");
+        assert_eq!(b.width, 4.0);
+        assert_eq!(b.doors, [Door::South].iter().copied().collect());
+        assert_eq!(b.edges, a.edges);
+
+        // the nearest template that sets a field wins
+        assert_eq!(c.description, "Again.");
+        assert_eq!(c.width, 8.0);
+        assert_eq!(c.depth, 6.0);
+        assert_eq!(c.doors, b.doors);
+    }
+
+    #[test]
+    fn unknown_template() {
+        let err = resolve(&format!(r#"{} (id: "b", template: "nope"),"#, BASE)).unwrap_err();
+        match err {
+            LoadError::UnknownTemplate { room, template } => {
+                assert_eq!((room.as_str(), template.as_str()), ("b", "nope"))
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn template_cycle() {
+        let err = resolve(
+            r#"(id: "a", template: "b"),
+            (id: "b", template: "c"),
+            (id: "c", template: "a"),"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, LoadError::TemplateCycle(room) if room == "a"));
+
+        let err = resolve(&format!(r#"{} (id: "b", template: "b"),"#, BASE)).unwrap_err();
+        assert!(matches!(err, LoadError::TemplateCycle(room) if room == "b"));
+    }
+
+    #[test]
+    fn missing_field() {
+        let err = resolve(
+            r#"(
+                id: "a",
+                name: "Base",
+                description: "No width.",
+                color: (r: 1.0, g: 1.0, b: 1.0, a: 1.0),
+                height: 2.0,
+                depth: 6.0,
+            ),"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::MissingField { room, field: "width" } if room == "a"
+        ));
+
+        // reported for the room using the template, not the template
+        let err = resolve(r#"(id: "a", template: "b"), (id: "b", name: "Base"),"#).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::MissingField { room, field: "description" } if room == "a"
+        ));
+    }
+
+    #[test]
+    fn duplicate_id() {
+        let err = resolve(&format!("{} {}", BASE, BASE)).unwrap_err();
+        assert!(matches!(err, LoadError::DuplicateId(id) if id == "a"));
     }
 }
@@ -1,7 +1,6 @@
-#![allow(incomplete_features)]
-#![feature(const_generics)]
-
+use std::env;
 use std::mem;
+use std::process;
 
 use bevy::math::*;
 use bevy::prelude::*;
@@ -11,55 +10,125 @@ use bevy::winit::WinitWindows;
 use bevy_fly_camera::{FlyCamera, FlyCameraPlugin};
 
 use character::*;
+use cli::{Dump, Mode, Options};
 use phys::*;
 use proc::*;
+use reload::*;
 use room::*;
+use save::*;
 
 pub mod array;
+pub mod bench;
 pub mod character;
+pub mod cli;
 pub mod faux;
 pub mod level;
+pub mod map;
 pub mod phys;
 pub mod proc;
+pub mod reload;
 pub mod room;
+pub mod save;
 pub mod text;
 
-pub const FAUX: bool = false;
-
 fn main() {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    if options.bench {
+        bench::run();
+        return;
+    }
+
+    let level = match &options.mode {
+        Mode::Level(path) => {
+            let mut level = match level::load(path) {
+                Ok(level) => level,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(2);
+                }
+            };
+            if let Some(start) = &options.start {
+                level.start = start.clone();
+            }
+            // cli::parse only allows dumping level files
+            if let Some(dump) = options.dump {
+                match dump {
+                    Dump::Dot => print!("{}", map::dot(&level)),
+                    Dump::Map => print!("{}", map::text(&level)),
+                }
+                return;
+            }
+            Some(level)
+        }
+        _ => None,
+    };
+
+    if let Some(level) = &level {
+        if !level::check(level) {
+            process::exit(2);
+        }
+    }
+
     let mut builder = App::build();
     builder
         .add_default_plugins()
         .add_plugin(FlyCameraPlugin)
-        .init_resource::<Friction>()
+        .init_resource::<PhysicsWorld>()
+        .init_resource::<BodyHandles>()
+        .add_resource(FixedTimestep::new(options.physics_hz))
         .init_resource::<SensorListenerState>()
+        .init_resource::<AutosaveState>()
+        .init_resource::<PendingDoors>()
         .add_resource(MouseGrabbed(true))
-        .add_event::<Manifold>()
+        .add_resource(LoadedLevel(level))
+        .add_event::<SensorEntered>()
+        .add_event::<SensorExited>()
+        .add_event::<RoomChanged>()
         .add_startup_system(setup.system());
-    if FAUX {
-        builder
-            .add_system_to_stage(stage::FIRST, character_controller_system.system())
-            .add_system_to_stage(stage::UPDATE, physics_system.system())
-            .add_system_to_stage(stage::UPDATE, joints_system.system())
-            .run()
-    } else {
-        builder
-            .add_system_to_stage(stage::LAST, room_system.system())
-            .add_system_to_stage(stage::LAST, visible_parent_system.system())
-            .add_system_to_stage(stage::FIRST, character_controller_system.system())
-            .add_system_to_stage(stage::POST_UPDATE, sensor_system.system())
-            .add_system_to_stage(stage::UPDATE, physics_system.system())
-            .add_system_to_stage(stage::UPDATE, joints_system.system())
-            .add_system_to_stage(stage::UPDATE, debug_draw_system.system())
-            .add_system_to_stage(stage::UPDATE, text::text_system.system())
-            .add_system_to_stage(stage::UPDATE, grab_mouse_system.system())
-            .run()
+    match &options.mode {
+        Mode::Faux => {
+            builder
+                .add_system_to_stage(stage::FIRST, character_controller_system.system())
+                .add_system_to_stage(stage::UPDATE, physics_system.system());
+        }
+        mode => {
+            if let Mode::Level(path) = mode {
+                builder
+                    .add_resource(LevelWatcher::new(path))
+                    .add_system_to_stage(stage::PRE_UPDATE, level_reload_system.system());
+            }
+            builder
+                .add_system_to_stage(stage::LAST, room_system.system())
+                .add_system_to_stage(stage::LAST, visible_parent_system.system())
+                .add_system_to_stage(stage::FIRST, character_controller_system.system())
+                .add_system_to_stage(stage::POST_UPDATE, sensor_system.system())
+                .add_system_to_stage(stage::POST_UPDATE, autosave_system.system())
+                .add_system_to_stage(stage::LAST, restore_doors_system.system())
+                .add_system_to_stage(stage::UPDATE, physics_system.system())
+                .add_system_to_stage(stage::UPDATE, debug_draw_system.system())
+                .add_system_to_stage(stage::UPDATE, text::text_system.system())
+                .add_system_to_stage(stage::UPDATE, grab_mouse_system.system());
+        }
     }
+    builder.add_resource(options).run()
 }
 
 fn setup(
     mut commands: Commands,
     assets: Res<AssetServer>,
+    options: Res<Options>,
+    mut loaded: ResMut<LoadedLevel>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut fonts: ResMut<Assets<Font>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -71,33 +140,87 @@ fn setup(
     let font = assets
         .load_sync(&mut fonts, "assets/font/TruenoLight-E2pg.ttf")
         .unwrap();
+    let mut level = match &options.mode {
+        Mode::Faux => None,
+        Mode::Level(_) => loaded.0.take(),
+        &Mode::Generate { seed, size } => {
+            println!("generating a level with seed {}", seed);
+            let params = Parameters {
+                seed,
+                size,
+                min_size: 4.0,
+                max_size: 16.0,
+                min_height: 2.0,
+                max_height: 2.0,
+                min_props: 0,
+                max_props: 3,
+                props: ["bed", "chair", "desk", "flower_table"]
+                    .iter()
+                    .map(|n| {
+                        let handle = assets
+                            .load_sync(&mut meshes, format!("assets/mesh/prop_{}.gltf", n))
+                            .unwrap();
+                        (n.to_string(), proc::footprint(meshes.get(&handle).unwrap()))
+                    })
+                    .collect(),
+                clone_probability: 1.0,
+                goal: true,
+            };
+            let mut level = proc::generate(&params);
+            if let Some(start) = &options.start {
+                level.start = start.clone();
+            }
+            if !level::check(&level) {
+                process::exit(2);
+            }
+            Some(level)
+        }
+    };
+
+    let mut controller = Character::default();
+    let body = RigidBody::new(Status::Semikinematic, 1.0, 0.5).layers(LAYER_PLAYER, LAYER_ALL);
+    let mut body = if options.round_player {
+        body.circle(Vec2::zero(), 0.1)
+    } else {
+        body.shape(Vec2::new(-0.1, -0.1), 0.2, 0.2)
+    };
+    if let Some(level) = &mut level {
+        let save = if options.new_game {
+            None
+        } else {
+            match SaveGame::load(SAVE) {
+                Ok(save) => Some(save),
+                Err(err) if err.is_missing() => None,
+                Err(err) => {
+                    eprintln!("warning: {}, starting a new game", err);
+                    None
+                }
+            }
+        };
+        let save = save.filter(|save| {
+            save.level == level_key(&options.mode)
+                && options.start.is_none()
+                && level.index(&save.room).is_some()
+        });
+        if let Some(save) = save {
+            level.start = save.room;
+            body.position = save.position;
+            body.velocity = save.velocity;
+            controller.set_rotation(save.yrot, save.xrot);
+            commands.insert_resource(PendingDoors(save.doors));
+        }
+        level::check_props(level, &proc::footprints(&assets, &mut meshes, level));
+    }
+
     let mut character = None;
-    let mut sensor = None;
-    let mut sensor_body =
-        RigidBody::new(Status::Semikinematic, 1.0, 0.5).shape(Vec2::new(-2.0, -2.0), 4.0, 4.0);
-    sensor_body.set_sensor(true);
     commands
         .spawn(CharBundle {
             global_transform: Default::default(),
             transform: Default::default(),
-            controller: Character::default(),
-            body: RigidBody::new(Status::Semikinematic, 1.0, 0.5).shape(
-                Vec2::new(-0.1, -0.1),
-                0.2,
-                0.2,
-            ),
+            controller,
+            body,
         })
         .for_current_entity(|e| character = Some(e))
-        .spawn(SensorBundle {
-            global_transform: Default::default(),
-            transform: Default::default(),
-            controller: Sensor {
-                character: character.unwrap(),
-            },
-            body: sensor_body,
-        })
-        .for_current_entity(|e| sensor = Some(e))
-        .spawn((Joint::new(character.unwrap(), sensor.unwrap()),))
         .spawn(LightComponents {
             transform: Transform::from_translation(Vec3::new(0.0, 1.4, 0.0)),
             ..Default::default()
@@ -164,26 +287,9 @@ fn setup(
                 .with(text::TextFrame(font, true))
                 .with_children(|_| {});
         });
-    // let params = Parameters {
-    //     size: 10,
-    //     min_size: 4.0,
-    //     max_size: 16.0,
-    //     min_height: 2.0,
-    //     max_height: 2.0,
-    //     min_props: 0,
-    //     max_props: 3,
-    //     props: ["bed", "chair", "desk", "flower_table"]
-    //         .iter()
-    //         .map(|n| n.to_string())
-    //         .collect(),
-    //     clone_probability: 1.0,
-    // };
-    // let level = proc::generate(&params);
-    if FAUX {
-        faux::spawn(&mut commands, &assets, &mut meshes, &mut materials);
-    } else {
-        let level = level::new();
-        proc::spawn(&mut commands, &assets, &mut meshes, &mut materials, &level);
+    match level {
+        None => faux::spawn(&mut commands, &assets, &mut meshes, &mut materials),
+        Some(level) => proc::spawn(&mut commands, &assets, &mut meshes, &mut materials, &level),
     }
 }
 
@@ -195,40 +301,30 @@ pub fn room_system(
     mut query: Query<(Entity, &Edges, &Name, &Props)>,
     mut is_active: Query<&ActiveRoom>,
     connected: Query<(Mut<RigidBody>, Mut<Draw>)>,
-    mut rooms: Query<With<RoomMarker, (Entity, &Props)>>,
-    mut connections: Query<(Entity, Mut<Connection>)>,
+    mut rooms: Query<With<RoomMarker, Entity>>,
+    mut islands: Query<(&Island, Mut<RigidBody>)>,
+    mut connections: Query<(Entity, Mut<Connection>, Mut<Joint>)>,
     mut frames: Query<(Entity, &text::TextFrame, &Children)>,
 ) {
     let any_active = is_active.iter().iter().count() != 0;
     if !any_active {
-        for (e, mut connection) in &mut connections.iter() {
+        for (e, mut connection, mut joint) in &mut connections.iter() {
             let mut body = connected.get_mut::<RigidBody>(e).unwrap();
-            body.set_active(false);
-            if connection.open {
-                body.rotation -= 90.0_f32.to_radians();
-                let rot = Mat2::from_angle(-body.rotation);
-                let offset = rot * Vec2::new(0.5, 0.5);
-                body.position -= offset;
-                body.set_sensor(false);
-                connection.open = false;
-            }
+            connection.snap_open(&mut body, &mut joint, false);
         }
-        for (e, props) in &mut rooms.iter() {
-            connected.get_mut::<RigidBody>(e).unwrap().set_active(false);
+        for e in &mut rooms.iter() {
+            sleep_island(&mut islands, e);
             connected.get_mut::<Draw>(e).unwrap().is_visible = false;
-            for &e in &props.vec {
-                connected.get_mut::<RigidBody>(e).unwrap().set_active(false);
-                connected.get_mut::<Draw>(e).unwrap().is_visible = false;
-            }
         }
     }
     let current = current.entity;
+    let entered = is_active.get::<ActiveRoom>(current).is_err();
     if let Ok(name) = query.get::<Name>(current) {
         let mut draw = connected.get_mut::<Draw>(current).unwrap();
         draw.is_visible = true;
         let mut body = connected.get_mut::<RigidBody>(current).unwrap();
         body.set_active(true);
-        if is_active.get::<ActiveRoom>(current).is_err() {
+        if entered {
             if name.get() == "Abjection" {
                 let music = assets.load("assets/sound/music.mp3").unwrap();
                 audio.play(music);
@@ -311,18 +407,14 @@ pub fn room_system(
         }
     }
 
-    if let Ok(doorset) = query.get::<DoorSet>(current) {
-        for &e in &doorset.vec {
-            let mut body = connected.get_mut::<RigidBody>(e).unwrap();
-            body.set_active(true);
-        }
-    }
-
-    if let Ok(props) = query.get::<Props>(current) {
-        for &e in &props.vec {
-            let mut body = connected.get_mut::<RigidBody>(e).unwrap();
-            body.set_active(true);
+    // props of the rooms that were left stay wherever they were pushed to
+    if entered {
+        for e in &mut rooms.iter() {
+            if e != current {
+                sleep_island(&mut islands, e);
+            }
         }
+        wake_island(&mut islands, current);
     }
 }
 
@@ -349,6 +441,9 @@ pub fn visible_parent_system(
 
 struct MouseGrabbed(bool);
 
+// loaded and checked in main, so a bad level is reported before the window opens
+struct LoadedLevel(Option<LevelPrototype>);
+
 fn grab_mouse_system(
     input: Res<Input<KeyCode>>,
     winit: Res<WinitWindows>,
@@ -0,0 +1,183 @@
+use std::fmt::Write;
+
+use crate::proc::*;
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn marker(level: &LevelPrototype, room: &RoomPrototype) -> &'static str {
+    if room.id == level.start {
+        " [start]"
+    } else if level.goal.as_ref() == Some(&room.id) {
+        " [goal]"
+    } else {
+        ""
+    }
+}
+
+pub fn dot(level: &LevelPrototype) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph level {{").unwrap();
+    writeln!(out, "    node [shape=box];").unwrap();
+    for room in &level.rooms {
+        let mut attrs = format!(
+            "label=\"{}\\n{}\\n{}x{}\"",
+            escape(&room.name),
+            escape(&room.description),
+            room.width,
+            room.depth
+        );
+        if room.id == level.start {
+            attrs.push_str(", peripheries=2");
+        }
+        if level.goal.as_ref() == Some(&room.id) {
+            attrs.push_str(", style=bold");
+        }
+        writeln!(out, "    \"{}\" [{}];", escape(&room.id), attrs).unwrap();
+    }
+    for room in &level.rooms {
+        for edge in &room.edges {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{:?} to {:?}\\n{}°\"];",
+                escape(&room.id),
+                escape(&edge.room),
+                edge.from,
+                edge.to,
+                edge.from.rotation(edge.to).to_degrees().round()
+            )
+            .unwrap();
+        }
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+pub fn text(level: &LevelPrototype) -> String {
+    let mut out = String::new();
+    for room in &level.rooms {
+        writeln!(
+            out,
+            "{} \"{}\" ({}x{}){}",
+            room.id,
+            room.name,
+            room.width,
+            room.depth,
+            marker(level, room)
+        )
+        .unwrap();
+        for door in Door::ALL.iter().filter(|door| room.doors.contains(door)) {
+            let mut edges = room
+                .edges
+                .iter()
+                .filter(|edge| edge.from == *door)
+                .peekable();
+            if edges.peek().is_none() {
+                writeln!(out, "    {:?} -> nowhere", door).unwrap();
+            }
+            for edge in edges {
+                writeln!(
+                    out,
+                    "    {:?} -> {} {:?} ({}°)",
+                    door,
+                    edge.room,
+                    edge.to,
+                    edge.from.rotation(edge.to).to_degrees().round()
+                )
+                .unwrap();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    fn room(id: &str, name: &str, description: &str, size: (f32, f32)) -> RoomPrototype {
+        RoomPrototype {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            color: Color::WHITE,
+            width: size.0,
+            depth: size.1,
+            height: 2.0,
+            doors: Default::default(),
+            edges: vec![],
+            props: vec![],
+        }
+    }
+
+    fn connect(room: &mut RoomPrototype, from: Door, to: &str, door: Door) {
+        room.doors.insert(from);
+        room.edges.push(EdgePrototype {
+            room: to.to_string(),
+            from,
+            to: door,
+        });
+    }
+
+    fn level() -> LevelPrototype {
+        let mut hall = room("hall", "Hall \"A\"", "C:\\hall", (4.0, 6.0));
+        connect(&mut hall, Door::North, "attic", Door::South);
+        connect(&mut hall, Door::East, "cellar", Door::North);
+        let mut attic = room("attic", "Attic", "Dusty", (3.0, 3.0));
+        connect(&mut attic, Door::South, "hall", Door::North);
+        let mut cellar = room("cellar", "Cellar", "Dark", (5.0, 2.5));
+        connect(&mut cellar, Door::North, "hall", Door::East);
+        cellar.doors.insert(Door::West);
+        LevelPrototype {
+            start: "hall".to_string(),
+            goal: Some("attic".to_string()),
+            rooms: vec![hall, attic, cellar],
+        }
+    }
+
+    #[test]
+    fn dot_golden() {
+        let expected = r#"digraph level {
+    node [shape=box];
+    "hall" [label="Hall \"A\"\nC:\\hall\n4x6", peripheries=2];
+    "attic" [label="Attic\nDusty\n3x3", style=bold];
+    "cellar" [label="Cellar\nDark\n5x2.5"];
+    "hall" -> "attic" [label="North to South\n0°"];
+    "hall" -> "cellar" [label="East to North\n270°"];
+    "attic" -> "hall" [label="South to North\n0°"];
+    "cellar" -> "hall" [label="North to East\n90°"];
+}
+"#;
+        assert_eq!(dot(&level()), expected);
+    }
+
+    #[test]
+    fn text_golden() {
+        let expected = r#"hall "Hall "A"" (4x6) [start]
+    North -> attic South (0°)
+    East -> cellar North (270°)
+attic "Attic" (3x3) [goal]
+    South -> hall North (0°)
+cellar "Cellar" (5x2.5)
+    North -> hall East (90°)
+    West -> nowhere
+"#;
+        assert_eq!(text(&level()), expected);
+    }
+
+    #[test]
+    fn start_wins_over_goal() {
+        let mut level = level();
+        level.goal = Some("hall".to_string());
+        assert_eq!(marker(&level, &level.rooms[0]), " [start]");
+        assert_eq!(marker(&level, &level.rooms[1]), "");
+    }
+
+    #[test]
+    fn escape_quotes_and_backslashes() {
+        assert_eq!(escape(r#"a\"b"#), r#"a\\\"b"#);
+    }
+}
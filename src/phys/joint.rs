@@ -0,0 +1,323 @@
+use bevy::math::*;
+use bevy::prelude::*;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy)]
+pub enum JointKind {
+    // keeps body2 at an offset and angle from body1
+    Fixed {
+        offset: Vec2,
+        angle: f32,
+    },
+    // keeps the anchors length apart, either rigidly or through a spring
+    Distance {
+        anchor1: Vec2,
+        anchor2: Vec2,
+        length: f32,
+        spring: Option<(f32, f32)>,
+    },
+    // pins the anchors together and lets body2 turn around them, reference is
+    // the angle of body2 relative to body1 that limits and motor are
+    // measured from
+    Hinge {
+        anchor1: Vec2,
+        anchor2: Vec2,
+        reference: f32,
+        limits: Option<(f32, f32)>,
+        motor: Option<f32>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Joint<B = Entity> {
+    body1: B,
+    body2: B,
+    kind: JointKind,
+}
+
+impl<B: Copy> Joint<B> {
+    pub fn new(body1: B, body2: B) -> Self {
+        Self {
+            body1,
+            body2,
+            kind: JointKind::Fixed {
+                offset: Vec2::zero(),
+                angle: 0.0,
+            },
+        }
+    }
+
+    pub fn distance(body1: B, body2: B, anchor1: Vec2, anchor2: Vec2, length: f32) -> Self {
+        Self {
+            body1,
+            body2,
+            kind: JointKind::Distance {
+                anchor1,
+                anchor2,
+                length,
+                spring: None,
+            },
+        }
+    }
+
+    pub fn hinge(body1: B, body2: B, anchor1: Vec2, anchor2: Vec2) -> Self {
+        Self {
+            body1,
+            body2,
+            kind: JointKind::Hinge {
+                anchor1,
+                anchor2,
+                reference: 0.0,
+                limits: None,
+                motor: None,
+            },
+        }
+    }
+
+    pub fn offset(mut self, offset: Vec2) -> Self {
+        if let JointKind::Fixed { offset: o, .. } = &mut self.kind {
+            *o = offset;
+        }
+        self
+    }
+
+    pub fn angle(mut self, angle: f32) -> Self {
+        if let JointKind::Fixed { angle: a, .. } = &mut self.kind {
+            *a = angle;
+        }
+        self
+    }
+
+    pub fn spring(mut self, stiffness: f32, damping: f32) -> Self {
+        if let JointKind::Distance { spring, .. } = &mut self.kind {
+            *spring = Some((stiffness, damping));
+        }
+        self
+    }
+
+    pub fn reference(mut self, angle: f32) -> Self {
+        if let JointKind::Hinge { reference, .. } = &mut self.kind {
+            *reference = angle;
+        }
+        self
+    }
+
+    pub fn limits(mut self, min: f32, max: f32) -> Self {
+        if let JointKind::Hinge { limits, .. } = &mut self.kind {
+            *limits = Some((min, max));
+        }
+        self
+    }
+
+    pub fn motor(mut self, speed: f32) -> Self {
+        self.set_motor(Some(speed));
+        self
+    }
+
+    pub fn set_motor(&mut self, speed: Option<f32>) {
+        if let JointKind::Hinge { motor, .. } = &mut self.kind {
+            *motor = speed;
+        }
+    }
+
+    pub fn bodies(&self) -> (B, B) {
+        (self.body1, self.body2)
+    }
+
+    pub fn kind(&self) -> JointKind {
+        self.kind
+    }
+
+    pub fn map<C, F: FnMut(B) -> Option<C>>(&self, mut f: F) -> Option<Joint<C>> {
+        Some(Joint {
+            body1: f(self.body1)?,
+            body2: f(self.body2)?,
+            kind: self.kind,
+        })
+    }
+}
+
+// how much of a joint's error each body takes up, a pair that can't be moved
+// by impulses leaves it all to body2 so it simply follows body1
+fn weights(body1: &RigidBody, body2: &RigidBody) -> (f32, f32) {
+    let w1 = effective_inv_mass(body1);
+    let w2 = effective_inv_mass(body2);
+    if w1 + w2 == 0.0 {
+        (0.0, 1.0)
+    } else {
+        (w1 / (w1 + w2), w2 / (w1 + w2))
+    }
+}
+
+pub(super) fn solve(
+    kind: JointKind,
+    body1: &mut RigidBody,
+    body2: &mut RigidBody,
+    delta_time: f32,
+) {
+    let (w1, w2) = weights(body1, body2);
+    match kind {
+        JointKind::Fixed { offset, angle } => {
+            let error = body1.position + offset - body2.position;
+            body1.position -= error * w1;
+            body2.position += error * w2;
+            let error = body1.rotation + angle - body2.rotation;
+            body1.rotation -= error * w1;
+            body2.rotation += error * w2;
+            let error = body1.velocity - body2.velocity;
+            body1.velocity -= error * w1;
+            body2.velocity += error * w2;
+        }
+        JointKind::Distance {
+            anchor1,
+            anchor2,
+            length,
+            spring,
+        } => {
+            let a1 = body1.position + rotate(anchor1, body1.rotation);
+            let a2 = body2.position + rotate(anchor2, body2.rotation);
+            let distance = (a2 - a1).length();
+            if distance == 0.0 {
+                return;
+            }
+            let normal = (a2 - a1) / distance;
+            let error = distance - length;
+            let speed = (body2.velocity - body1.velocity).dot(normal);
+            match spring {
+                None => {
+                    body1.position += normal * error * w1;
+                    body2.position -= normal * error * w2;
+                    body1.velocity += normal * speed * w1;
+                    body2.velocity -= normal * speed * w2;
+                }
+                Some((stiffness, damping)) => {
+                    let impulse = (-stiffness * error - damping * speed) * delta_time;
+                    body1.velocity -= normal * impulse * w1;
+                    body2.velocity += normal * impulse * w2;
+                }
+            }
+        }
+        JointKind::Hinge {
+            anchor1,
+            anchor2,
+            reference,
+            limits,
+            motor,
+        } => {
+            if let Some(speed) = motor {
+                let error = speed - (body2.angular_velocity - body1.angular_velocity);
+                body1.angular_velocity -= error * w1;
+                body2.angular_velocity += error * w2;
+            }
+            if let Some((min, max)) = limits {
+                let angle = body2.rotation - body1.rotation - reference;
+                let clamped = angle.max(min).min(max);
+                if clamped != angle {
+                    let error = clamped - angle;
+                    body1.rotation -= error * w1;
+                    body2.rotation += error * w2;
+                    let spin = body2.angular_velocity - body1.angular_velocity;
+                    if spin * error < 0.0 {
+                        body1.angular_velocity += spin * w1;
+                        body2.angular_velocity -= spin * w2;
+                    }
+                }
+            }
+            let a1 = body1.position + rotate(anchor1, body1.rotation);
+            let a2 = body2.position + rotate(anchor2, body2.rotation);
+            let error = a1 - a2;
+            body1.position -= error * w1;
+            body2.position += error * w2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchor() -> RigidBody {
+        RigidBody::new(Status::Static, INF_MASS, 0.0)
+    }
+
+    fn free() -> RigidBody {
+        RigidBody::new(Status::Dynamic, 1.0, 0.0)
+    }
+
+    #[test]
+    fn hinge_limits_clamp() {
+        let (mut body1, mut body2) = (anchor(), free());
+        body2.rotation = 1.0;
+        body2.angular_velocity = 2.0;
+        let joint = Joint::hinge((), (), Vec2::zero(), Vec2::zero()).limits(-0.5, 0.5);
+
+        solve(joint.kind(), &mut body1, &mut body2, 1.0 / 60.0);
+
+        assert_eq!(body1.rotation, 0.0);
+        assert!((body2.rotation - 0.5).abs() < 1e-6);
+        // spinning further out of the limits is stopped
+        assert_eq!(body2.angular_velocity, 0.0);
+    }
+
+    #[test]
+    fn hinge_limits_keep_spin_back_in() {
+        let (mut body1, mut body2) = (anchor(), free());
+        body2.rotation = -1.0;
+        body2.angular_velocity = 2.0;
+        let joint = Joint::hinge((), (), Vec2::zero(), Vec2::zero())
+            .reference(0.5)
+            .limits(-0.5, 0.5);
+
+        solve(joint.kind(), &mut body1, &mut body2, 1.0 / 60.0);
+
+        assert!(body2.rotation.abs() < 1e-6);
+        assert_eq!(body2.angular_velocity, 2.0);
+    }
+
+    #[test]
+    fn hinge_motor_drives() {
+        let (mut body1, mut body2) = (anchor(), free());
+        body2.position = Vec2::new(5.0, 5.0);
+        let joint = Joint::hinge((), (), Vec2::new(1.0, 0.0), Vec2::zero()).motor(3.0);
+
+        solve(joint.kind(), &mut body1, &mut body2, 1.0 / 60.0);
+
+        assert_eq!(body1.angular_velocity, 0.0);
+        assert!((body2.angular_velocity - 3.0).abs() < 1e-6);
+        // the anchors are pinned together
+        assert!((body2.position - Vec2::new(1.0, 0.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn distance_rest_length() {
+        let (mut body1, mut body2) = (anchor(), free());
+        body2.position = Vec2::new(3.0, 0.0);
+        body2.velocity = Vec2::new(1.0, 1.0);
+        let joint = Joint::distance((), (), Vec2::zero(), Vec2::zero(), 2.0);
+
+        solve(joint.kind(), &mut body1, &mut body2, 1.0 / 60.0);
+
+        assert_eq!(body1.position, Vec2::zero());
+        assert!((body2.position - Vec2::new(2.0, 0.0)).length() < 1e-6);
+        // only the velocity along the joint is taken away
+        assert!((body2.velocity - Vec2::new(0.0, 1.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn distance_spring_settles() {
+        let mut world = PhysicsWorld::new();
+        let body1 = world.insert(anchor());
+        let body2 = world.insert(free().position(Vec2::new(3.0, 0.0)));
+        world.add_joint(
+            Joint::distance(body1, body2, Vec2::zero(), Vec2::zero(), 2.0).spring(50.0, 5.0),
+        );
+
+        for _ in 0..600 {
+            world.step(1.0 / 60.0);
+        }
+
+        let distance = world.get(body2).unwrap().position.length();
+        assert!((distance - 2.0).abs() < 0.01, "distance is {}", distance);
+    }
+}
@@ -0,0 +1,1327 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::math::*;
+use bevy::prelude::*;
+use bevy::render::{mesh::*, pipeline::PrimitiveTopology, prelude::*};
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::array::Array;
+
+pub use joint::{Joint, JointKind};
+pub use world::{BodyHandle, Contact, PhysicsWorld, RayHit, SensorEvent};
+
+pub mod joint;
+pub mod world;
+
+pub const INF_MASS: f32 = 0.0;
+
+// turns v the same way Quat::from_rotation_y(rotation) turns a body's
+// transform, with y standing in for z
+pub fn rotate(v: Vec2, rotation: f32) -> Vec2 {
+    Mat2::from_angle(-rotation) * v
+}
+
+// collision layers, two bodies only touch if each one's category is in the
+// other's mask
+pub const LAYER_DEFAULT: u32 = 1 << 0;
+pub const LAYER_PLAYER: u32 = 1 << 1;
+pub const LAYER_DOOR: u32 = 1 << 2;
+pub const LAYER_ROOM_SENSOR: u32 = 1 << 3;
+pub const LAYER_ALL: u32 = !0;
+
+// dynamic bodies slower than this for SLEEP_TIME seconds fall asleep
+pub const SLEEP_VELOCITY: f32 = 0.05;
+pub const SLEEP_ANGULAR_VELOCITY: f32 = 0.05;
+pub const SLEEP_TIME: f32 = 0.5;
+
+// how two materials' values are mixed, when the modes differ the one that
+// comes later wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Combine {
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl Combine {
+    pub fn combine(self, other: Combine, a: f32, b: f32) -> f32 {
+        match self.max(other) {
+            Combine::Average => (a + b) / 2.0,
+            Combine::Min => a.min(b),
+            Combine::Multiply => a * b,
+            Combine::Max => a.max(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsMaterial {
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+    pub friction_combine: Combine,
+    pub restitution: f32,
+    pub restitution_combine: Combine,
+    // velocity lost per second, as in v *= exp(-linear_damping * t)
+    pub linear_damping: f32,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            static_friction: 0.6,
+            dynamic_friction: 0.4,
+            friction_combine: Combine::Average,
+            restitution: 0.5,
+            restitution_combine: Combine::Min,
+            // keeps 95% of the velocity every 60th of a second
+            linear_damping: -0.95_f32.ln() * 60.0,
+        }
+    }
+}
+
+impl PhysicsMaterial {
+    pub fn restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn friction(mut self, static_friction: f32, dynamic_friction: f32) -> Self {
+        self.static_friction = static_friction;
+        self.dynamic_friction = dynamic_friction;
+        self
+    }
+
+    pub fn damping(mut self, linear_damping: f32) -> Self {
+        self.linear_damping = linear_damping;
+        self
+    }
+
+    pub fn damping_over(&self, time: f32) -> f32 {
+        (-self.linear_damping * time).exp()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimestep {
+    pub hz: f32,
+    pub max_steps: u32,
+    accumulator: f32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(hz: f32) -> Self {
+        Self {
+            hz,
+            max_steps: 8,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn step(&self) -> f32 {
+        self.hz.recip()
+    }
+
+    // how many steps to run for a frame of the given length, time that does
+    // not fit into max_steps is dropped so a long hitch does not snowball
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        let step = self.step();
+        self.accumulator += delta;
+        let steps = (self.accumulator / step) as u32;
+        self.accumulator -= steps as f32 * step;
+        if steps > self.max_steps {
+            self.accumulator = 0.0;
+            self.max_steps
+        } else {
+            steps
+        }
+    }
+
+    // how far between the last two steps the frame is
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator * self.hz).min(1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x() < other.max.x()
+            && other.min.x() < self.max.x()
+            && self.min.y() < other.max.y()
+            && other.min.y() < self.max.y()
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x() <= other.min.x()
+            && other.max.x() <= self.max.x()
+            && self.min.y() <= other.min.y()
+            && other.max.y() <= self.max.y()
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    // time in 0..1 at which self moving by motion first touches other, and the
+    // normal of the face of other it hits
+    pub fn sweep(&self, motion: Vec2, other: &Aabb) -> Option<(f32, Vec2)> {
+        let half = (self.max - self.min) * 0.5;
+        let center = self.min + half;
+        let min = other.min - half;
+        let max = other.max + half;
+
+        let mut enter = f32::NEG_INFINITY;
+        let mut exit = f32::INFINITY;
+        let mut normal = Vec2::zero();
+        for &(c, d, lo, hi, axis) in &[
+            (
+                center.x(),
+                motion.x(),
+                min.x(),
+                max.x(),
+                Vec2::new(1.0, 0.0),
+            ),
+            (
+                center.y(),
+                motion.y(),
+                min.y(),
+                max.y(),
+                Vec2::new(0.0, 1.0),
+            ),
+        ] {
+            if d == 0.0 {
+                if c <= lo || c >= hi {
+                    return None;
+                }
+                continue;
+            }
+            let (t1, t2) = ((lo - c) / d, (hi - c) / d);
+            let (near, far) = (t1.min(t2), t1.max(t2));
+            if near > enter {
+                enter = near;
+                normal = axis * -d.signum();
+            }
+            exit = exit.min(far);
+        }
+        if enter <= exit && (0.0..=1.0).contains(&enter) {
+            Some((enter, normal))
+        } else {
+            None
+        }
+    }
+
+    pub fn transform(&self, position: Vec2, rotation: f32) -> Aabb {
+        let corners = [
+            self.min,
+            Vec2::new(self.max.x(), self.min.y()),
+            self.max,
+            Vec2::new(self.min.x(), self.max.y()),
+        ];
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+        for &corner in &corners {
+            let corner = position + rotate(corner, rotation);
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+        Aabb { min, max }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Rect {
+        offset: Vec2,
+        width: f32,
+        height: f32,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+    },
+    Capsule {
+        a: Vec2,
+        b: Vec2,
+        radius: f32,
+    },
+    Polygon {
+        points: Array<Vec2, 8>,
+    },
+}
+
+impl Shape {
+    // every shape is a convex core (a point, a segment or a polygon) grown by
+    // a radius
+    fn hull(&self, position: Vec2, rotation: f32) -> Hull {
+        let mut points = Array::new();
+        let radius = match *self {
+            Shape::Rect {
+                offset,
+                width,
+                height,
+            } => {
+                let max = offset + Vec2::new(width, height);
+                points.push(offset);
+                points.push(Vec2::new(max.x(), offset.y()));
+                points.push(max);
+                points.push(Vec2::new(offset.x(), max.y()));
+                0.0
+            }
+            Shape::Circle { center, radius } => {
+                points.push(center);
+                radius
+            }
+            Shape::Capsule { a, b, radius } => {
+                points.push(a);
+                points.push(b);
+                radius
+            }
+            Shape::Polygon {
+                points: ref polygon,
+            } => {
+                points = polygon.clone();
+                0.0
+            }
+        };
+        for point in points.iter_mut() {
+            *point = position + rotate(*point, rotation);
+        }
+        Hull { points, radius }
+    }
+
+    pub fn aabb(&self, position: Vec2, rotation: f32) -> Aabb {
+        let hull = self.hull(position, rotation);
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+        for &point in hull.points.iter() {
+            min = min.min(point);
+            max = max.max(point);
+        }
+        let radius = Vec2::splat(hull.radius);
+        Aabb::new(min - radius, max + radius)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Hull {
+    points: Array<Vec2, 8>,
+    radius: f32,
+}
+
+impl Hull {
+    fn edges<'a>(&'a self) -> impl Iterator<Item = (Vec2, Vec2)> + Clone + 'a {
+        let points = self.points.as_slice();
+        let n = if points.len() < 3 { 1 } else { points.len() };
+        (0..n).map(move |i| (points[i], points[(i + 1) % points.len()]))
+    }
+
+    fn center(&self) -> Vec2 {
+        self.points.iter().fold(Vec2::zero(), |sum, &p| sum + p) / self.points.len() as f32
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        if self.points.len() < 3 {
+            return false;
+        }
+        let mut positive = false;
+        let mut negative = false;
+        for (p, q) in self.edges() {
+            let side = cross(q - p, point - p);
+            positive |= side > 0.0;
+            negative |= side < 0.0;
+        }
+        !(positive && negative)
+    }
+
+    fn overlaps(&self, other: &Hull) -> bool {
+        self.contains(other.points[0])
+            || other.contains(self.points[0])
+            || self
+                .edges()
+                .cartesian_product(other.edges())
+                .any(|((p1, p2), (q1, q2))| segments_intersect(p1, p2, q1, q2))
+    }
+
+    // distance along a normalized ray to the surface and the surface normal
+    // there, a ray starting inside hits right away
+    fn raycast(&self, origin: Vec2, dir: Vec2) -> Option<(f32, Vec2)> {
+        let inside = self.contains(origin)
+            || self
+                .edges()
+                .any(|(p, q)| (origin - closest_on_segment(origin, p, q)).length() <= self.radius);
+        if inside {
+            return Some((0.0, -dir));
+        }
+        let mut best: Option<(f32, Vec2)> = None;
+        let mut hit = |t: f32, normal: Vec2| {
+            if t >= 0.0 && best.is_none_or(|(best, _)| t < best) {
+                best = Some((t, normal));
+            }
+        };
+        for &point in self.points.iter() {
+            if self.radius > 0.0 {
+                if let Some(t) = ray_circle(origin, dir, point, self.radius) {
+                    hit(t, (origin + dir * t - point).normalize());
+                }
+            }
+        }
+        for (p, q) in self.edges() {
+            let edge = q - p;
+            if edge.length_squared() == 0.0 {
+                continue;
+            }
+            let normal = Vec2::new(edge.y(), -edge.x()).normalize();
+            for &normal in &[normal, -normal] {
+                let offset = normal * self.radius;
+                if let Some(t) = ray_segment(origin, dir, p + offset, q + offset) {
+                    hit(t, normal);
+                }
+            }
+        }
+        best
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        let (min, max) =
+            self.points
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| {
+                    let d = p.dot(axis);
+                    (min.min(d), max.max(d))
+                });
+        (min - self.radius, max + self.radius)
+    }
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x() * b.y() - a.y() * b.x()
+}
+
+fn segments_intersect(p1: Vec2, p2: Vec2, q1: Vec2, q2: Vec2) -> bool {
+    let d1 = cross(q2 - q1, p1 - q1);
+    let d2 = cross(q2 - q1, p2 - q1);
+    let d3 = cross(p2 - p1, q1 - p1);
+    let d4 = cross(p2 - p1, q2 - p1);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    let on = |a: Vec2, b: Vec2, p: Vec2, d: f32| {
+        d == 0.0 && p.min(a.min(b)) == a.min(b) && p.max(a.max(b)) == a.max(b)
+    };
+    on(q1, q2, p1, d1) || on(q1, q2, p2, d2) || on(p1, p2, q1, d3) || on(p1, p2, q2, d4)
+}
+
+fn ray_segment(origin: Vec2, dir: Vec2, a: Vec2, b: Vec2) -> Option<f32> {
+    let edge = b - a;
+    let denominator = cross(dir, edge);
+    if denominator == 0.0 {
+        return None;
+    }
+    let t = cross(a - origin, edge) / denominator;
+    let u = cross(a - origin, dir) / denominator;
+    if t >= 0.0 && (0.0..=1.0).contains(&u) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+fn ray_circle(origin: Vec2, dir: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let to_origin = origin - center;
+    let b = to_origin.dot(dir);
+    let c = to_origin.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        None
+    } else {
+        Some(-b - discriminant.sqrt())
+    }
+}
+
+fn closest_on_segment(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length = ab.length_squared();
+    if length == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(ab) / length).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+// closest points between two cores that do not overlap
+fn closest(a: &Hull, b: &Hull) -> (Vec2, Vec2) {
+    let mut best = (a.points[0], b.points[0]);
+    let mut distance = f32::INFINITY;
+    for ((p1, p2), (q1, q2)) in a.edges().cartesian_product(b.edges()) {
+        let candidates = [
+            (p1, closest_on_segment(p1, q1, q2)),
+            (p2, closest_on_segment(p2, q1, q2)),
+            (closest_on_segment(q1, p1, p2), q1),
+            (closest_on_segment(q2, p1, p2), q2),
+        ];
+        for &(p, q) in &candidates {
+            let d = (q - p).length_squared();
+            if d < distance {
+                distance = d;
+                best = (p, q);
+            }
+        }
+    }
+    best
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Status {
+    Dynamic,
+    #[default]
+    Static,
+    Semikinematic,
+}
+
+#[derive(Debug, Clone)]
+pub struct RigidBody {
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+    pub angular_velocity: f32,
+    pub accumulator: Vec2,
+    pub shape: Array<Shape, 8>,
+    pub status: Status,
+    pub inv_mass: f32,
+    pub material: PhysicsMaterial,
+    pub active: bool,
+    pub sensor: bool,
+    pub category: u32,
+    pub mask: u32,
+    sleeping: bool,
+    // how long the body has been resting for
+    rest_time: f32,
+    // state before and after the last step, to interpolate between
+    previous: (Vec2, f32),
+    stepped: (Vec2, f32),
+}
+
+impl RigidBody {
+    pub fn new(status: Status, mass: f32, restitution: f32) -> Self {
+        Self {
+            position: Vec2::zero(),
+            rotation: 0.0,
+            velocity: Vec2::zero(),
+            angular_velocity: 0.0,
+            accumulator: Vec2::zero(),
+            shape: Array::new(),
+            status,
+            inv_mass: if mass == INF_MASS { 0.0 } else { mass.recip() },
+            material: PhysicsMaterial::default().restitution(restitution),
+            active: true,
+            sensor: false,
+            category: LAYER_DEFAULT,
+            mask: LAYER_ALL,
+            sleeping: false,
+            rest_time: 0.0,
+            previous: (Vec2::zero(), 0.0),
+            stepped: (Vec2::zero(), 0.0),
+        }
+    }
+
+    pub fn interpolated(&self, alpha: f32) -> (Vec2, f32) {
+        let (position, rotation) = self.previous;
+        (
+            position + (self.position - position) * alpha,
+            rotation + (self.rotation - rotation) * alpha,
+        )
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    pub fn sleep(&mut self) {
+        self.sleeping = true;
+        self.velocity = Vec2::zero();
+        self.angular_velocity = 0.0;
+    }
+
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.rest_time = 0.0;
+    }
+
+    fn is_resting(&self) -> bool {
+        self.velocity.length_squared() < SLEEP_VELOCITY * SLEEP_VELOCITY
+            && self.angular_velocity.abs() < SLEEP_ANGULAR_VELOCITY
+    }
+
+    // whether touching this body wakes a sleeping one up, static bodies only do
+    // when something moves them, like a door swinging on its hinge
+    fn wakes_others(&self) -> bool {
+        !self.sleeping && (self.status != Status::Static || !self.is_resting())
+    }
+
+    pub fn set_sensor(&mut self, sensor: bool) {
+        self.sensor = sensor;
+    }
+
+    pub fn material(mut self, material: PhysicsMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn layers(mut self, category: u32, mask: u32) -> Self {
+        self.category = category;
+        self.mask = mask;
+        self
+    }
+
+    pub fn position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn shape(mut self, offset: Vec2, width: f32, height: f32) -> Self {
+        self.shape.push(Shape::Rect {
+            offset,
+            width,
+            height,
+        });
+        self
+    }
+
+    pub fn circle(mut self, center: Vec2, radius: f32) -> Self {
+        self.shape.push(Shape::Circle { center, radius });
+        self
+    }
+
+    pub fn capsule(mut self, a: Vec2, b: Vec2, radius: f32) -> Self {
+        self.shape.push(Shape::Capsule { a, b, radius });
+        self
+    }
+
+    pub fn polygon(mut self, points: &[Vec2]) -> Self {
+        assert!(
+            points.len() >= 3,
+            "a polygon needs at least 3 points, got {:?}",
+            points.len()
+        );
+        let mut polygon = Array::new();
+        for &point in points {
+            polygon.push(point);
+        }
+        self.shape.push(Shape::Polygon { points: polygon });
+        self
+    }
+
+    pub fn aabbs(&self) -> impl Iterator<Item = Aabb> + Clone + '_ {
+        let (position, rotation) = (self.position, self.rotation);
+        self.shape
+            .iter()
+            .map(move |shape| shape.aabb(position, rotation))
+    }
+
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.aabbs().fold1(|a, b| a.union(&b))
+    }
+}
+
+fn may_collide(body1: &RigidBody, body2: &RigidBody) -> bool {
+    body1.active
+        && body2.active
+        && (body1.status != Status::Static || body2.status != Status::Static)
+        && body1.category & body2.mask != 0
+        && body2.category & body1.mask != 0
+}
+
+// checks every pair, kept around as a reference for the broad phase
+pub fn naive_pairs(bodies: &[RigidBody]) -> Vec<(usize, usize)> {
+    let aabbs = bodies.iter().map(RigidBody::aabb).collect::<Vec<_>>();
+    let mut pairs = Vec::new();
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            if !may_collide(&bodies[i], &bodies[j]) {
+                continue;
+            }
+            if let (Some(a), Some(b)) = (aabbs[i], aabbs[j]) {
+                if a.overlaps(&b) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+// sweep and prune along x, returns the same pairs as naive_pairs with i < j
+pub fn broad_phase(bodies: &[RigidBody]) -> Vec<(usize, usize)> {
+    let mut boxes = bodies
+        .iter()
+        .enumerate()
+        .filter(|(_, body)| body.active)
+        .filter_map(|(i, body)| body.aabb().map(|aabb| (i, aabb)))
+        .collect::<Vec<_>>();
+    boxes.sort_by(|(_, a), (_, b)| a.min.x().partial_cmp(&b.min.x()).unwrap());
+
+    let mut pairs = Vec::new();
+    let mut open: Vec<(usize, Aabb)> = Vec::new();
+    for (i, aabb) in boxes {
+        open.retain(|(_, other)| other.max.x() > aabb.min.x());
+        for &(j, other) in &open {
+            if aabb.overlaps(&other) && may_collide(&bodies[i], &bodies[j]) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+        open.push((i, aabb));
+    }
+    pairs.sort();
+    pairs
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SensorEntered {
+    pub sensor: Entity,
+    pub other: Entity,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SensorExited {
+    pub sensor: Entity,
+    pub other: Entity,
+}
+
+fn is_axis_aligned(rotation: f32) -> bool {
+    let r = rotation.rem_euclid(FRAC_PI_2);
+    r < 1e-4 || FRAC_PI_2 - r < 1e-4
+}
+
+fn aabb_contact(a: Aabb, b: Aabb) -> Option<(Vec2, f32)> {
+    let d = (b.min + b.max) * 0.5 - (a.min + a.max) * 0.5;
+    let x_overlap = a.max.x().min(b.max.x()) - a.min.x().max(b.min.x());
+    let y_overlap = a.max.y().min(b.max.y()) - a.min.y().max(b.min.y());
+    if x_overlap <= 0.0 || y_overlap <= 0.0 {
+        None
+    } else if x_overlap < y_overlap {
+        let n_x = if d.x() < 0.0 { -1.0 } else { 1.0 };
+        Some((Vec2::new(n_x, 0.0), x_overlap))
+    } else {
+        let n_y = if d.y() < 0.0 { -1.0 } else { 1.0 };
+        Some((Vec2::new(0.0, n_y), y_overlap))
+    }
+}
+
+// separating axis test on two overlapping cores
+fn sat(a: &Hull, b: &Hull) -> Option<(Vec2, f32)> {
+    let mut normal = Vec2::new(1.0, 0.0);
+    let mut depth = a.radius + b.radius;
+    let mut found = false;
+    let centers = b.center() - a.center();
+    let axes = a
+        .edges()
+        .chain(b.edges())
+        .map(|(p, q)| Vec2::new(p.y() - q.y(), q.x() - p.x()))
+        .chain(Some(centers));
+    for axis in axes {
+        if axis.length_squared() == 0.0 {
+            continue;
+        }
+        let axis = axis.normalize();
+        let (a_min, a_max) = a.project(axis);
+        let (b_min, b_max) = b.project(axis);
+        let overlap = a_max.min(b_max) - a_min.max(b_min);
+        if overlap <= 0.0 {
+            return None;
+        }
+        if !found || overlap < depth {
+            found = true;
+            depth = overlap;
+            normal = axis;
+        }
+    }
+    if centers.dot(normal) < 0.0 {
+        normal = -normal;
+    }
+    Some((normal, depth))
+}
+
+fn contact(a: &Hull, b: &Hull) -> Option<(Vec2, f32)> {
+    if a.overlaps(b) {
+        return sat(a, b);
+    }
+    let radius = a.radius + b.radius;
+    if radius == 0.0 {
+        return None;
+    }
+    let (p, q) = closest(a, b);
+    let distance = (q - p).length();
+    if distance >= radius || distance == 0.0 {
+        None
+    } else {
+        Some(((q - p) / distance, radius - distance))
+    }
+}
+
+// yields the normal, pointing from body1 to body2, and depth of every
+// overlapping pair of shapes
+pub fn collide<'a>(
+    body1: &'a RigidBody,
+    body2: &'a RigidBody,
+) -> impl Iterator<Item = (Vec2, f32)> + 'a {
+    let aligned = is_axis_aligned(body1.rotation) && is_axis_aligned(body2.rotation);
+
+    body1
+        .shape
+        .iter()
+        .cartesian_product(body2.shape.iter())
+        .filter_map(move |(a, b)| match (a, b) {
+            (Shape::Rect { .. }, Shape::Rect { .. }) if aligned => aabb_contact(
+                a.aabb(body1.position, body1.rotation),
+                b.aabb(body2.position, body2.rotation),
+            ),
+            _ => contact(
+                &a.hull(body1.position, body1.rotation),
+                &b.hull(body2.position, body2.rotation),
+            ),
+        })
+}
+
+// how far a swept body stops short of what it hits
+const SKIN: f32 = 0.001;
+
+// a body moving less than half its own size in a step can't pass through
+// anything without overlapping it at the end of some step
+fn needs_sweep(body: &RigidBody, motion: Vec2) -> bool {
+    if body.status == Status::Static || body.sensor {
+        return false;
+    }
+    match body.aabb() {
+        Some(aabb) => {
+            let size = aabb.max - aabb.min;
+            motion.x().abs() > size.x() / 2.0 || motion.y().abs() > size.y() / 2.0
+        }
+        None => false,
+    }
+}
+
+// earliest hit of body i moving by motion against the static bodies
+fn time_of_impact(
+    bodies: &[RigidBody],
+    i: usize,
+    body: &RigidBody,
+    motion: Vec2,
+) -> Option<(f32, Vec2)> {
+    let mut best: Option<(f32, Vec2)> = None;
+    for (j, other) in bodies.iter().enumerate() {
+        if j == i || other.status != Status::Static || other.sensor || !may_collide(body, other) {
+            continue;
+        }
+        for (a, b) in body.aabbs().cartesian_product(other.aabbs()) {
+            // already touching, the discrete step takes care of it
+            if a.overlaps(&b) {
+                continue;
+            }
+            if let Some((t, normal)) = a.sweep(motion, &b) {
+                if best.is_none_or(|(best, _)| t < best) {
+                    best = Some((t, normal));
+                }
+            }
+        }
+    }
+    best
+}
+
+// moves body i by motion without passing through static bodies, sliding
+// along whatever it hits
+fn sweep(bodies: &[RigidBody], i: usize, mut motion: Vec2) -> Vec2 {
+    let mut body = bodies[i].clone();
+    for _ in 0..2 {
+        match time_of_impact(bodies, i, &body, motion) {
+            None => {
+                body.position += motion;
+                break;
+            }
+            Some((t, normal)) => {
+                let length = motion.length();
+                let t = (t - SKIN / length).max(0.0);
+                body.position += motion * t;
+                let rest = motion * (1.0 - t);
+                motion = rest - normal * rest.dot(normal);
+            }
+        }
+    }
+    body.position
+}
+
+fn effective_inv_mass(body: &RigidBody) -> f32 {
+    match body.status {
+        Status::Dynamic => body.inv_mass,
+        Status::Static | Status::Semikinematic => 0.0,
+    }
+}
+
+// moves a semikinematic body out of whatever it hit and stops it from moving
+// any further into it, which makes it slide along walls. pushes along the same
+// direction from several shapes are not summed up.
+fn push_out(body: &mut RigidBody, correction: &mut Vec2, direction: Vec2, depth: f32) {
+    let done = correction.dot(direction);
+    if depth > done {
+        *correction += direction * (depth - done);
+    }
+    let into = -body.velocity.dot(direction);
+    if into > 0.0 {
+        body.velocity += direction * into;
+    }
+}
+
+fn resolve(
+    bodies: &mut [RigidBody],
+    corrections: &mut [Vec2],
+    i: usize,
+    j: usize,
+    normal: Vec2,
+    depth: f32,
+) {
+    let inv_a = effective_inv_mass(&bodies[i]);
+    let inv_b = effective_inv_mass(&bodies[j]);
+    let inv_sum = inv_a + inv_b;
+
+    if inv_sum > 0.0 {
+        let vn = (bodies[j].velocity - bodies[i].velocity).dot(normal);
+        if vn < 0.0 {
+            let (ma, mb) = (bodies[i].material, bodies[j].material);
+            let restitution = ma.restitution_combine.combine(
+                mb.restitution_combine,
+                ma.restitution,
+                mb.restitution,
+            );
+            let impulse = -(1.0 + restitution) * vn / inv_sum;
+            bodies[i].velocity -= normal * impulse * inv_a;
+            bodies[j].velocity += normal * impulse * inv_b;
+
+            // coulomb friction along the contact tangent, bounded by the normal
+            // impulse so it is only applied while the bodies are closing in and
+            // bodies already sliding apart keep their tangent velocity,
+            // semikinematic bodies are left out so the player keeps sliding
+            // along walls
+            let rv = bodies[j].velocity - bodies[i].velocity;
+            let tangent = rv - normal * rv.dot(normal);
+            if tangent.length_squared() > 0.0 {
+                let tangent = tangent.normalize();
+                let combine =
+                    |x: f32, y: f32| ma.friction_combine.combine(mb.friction_combine, x, y);
+                let static_friction = combine(ma.static_friction, mb.static_friction);
+                let dynamic_friction = combine(ma.dynamic_friction, mb.dynamic_friction);
+                let mut friction = -rv.dot(tangent) / inv_sum;
+                if friction.abs() > impulse * static_friction {
+                    friction = friction.signum() * impulse * dynamic_friction;
+                }
+                bodies[i].velocity -= tangent * friction * inv_a;
+                bodies[j].velocity += tangent * friction * inv_b;
+            }
+        }
+        let percent = 0.2;
+        let slop = 0.01;
+        let correction = normal * (depth - slop).max(0.0) / inv_sum * percent;
+        bodies[i].position -= correction * inv_a;
+        bodies[j].position += correction * inv_b;
+    }
+
+    let (a, b) = (bodies[i].status, bodies[j].status);
+    let a_pushes = a == Status::Semikinematic && b != Status::Dynamic;
+    let b_pushes = b == Status::Semikinematic && a != Status::Dynamic;
+    let depth = if a_pushes && b_pushes {
+        depth / 2.0
+    } else {
+        depth
+    };
+    if a_pushes {
+        push_out(&mut bodies[i], &mut corrections[i], -normal, depth);
+    }
+    if b_pushes {
+        push_out(&mut bodies[j], &mut corrections[j], normal, depth);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BodyHandles {
+    handles: HashMap<Entity, BodyHandle>,
+    entities: HashMap<BodyHandle, Entity>,
+}
+
+impl BodyHandles {
+    pub fn handle(&self, entity: Entity) -> Option<BodyHandle> {
+        self.handles.get(&entity).copied()
+    }
+
+    pub fn entity(&self, handle: BodyHandle) -> Option<Entity> {
+        self.entities.get(&handle).copied()
+    }
+}
+
+// bodies that are taken out of and put back into the simulation together, like
+// everything belonging to a room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Island(pub Entity);
+
+// the bodies of a sleeping island are inactive and keep their place until the
+// island is woken up again
+pub fn sleep_island(bodies: &mut Query<(&Island, Mut<RigidBody>)>, island: Entity) {
+    for (&Island(e), mut body) in &mut bodies.iter() {
+        if e == island {
+            body.set_active(false);
+            body.sleep();
+        }
+    }
+}
+
+pub fn wake_island(bodies: &mut Query<(&Island, Mut<RigidBody>)>, island: Entity) {
+    for (&Island(e), mut body) in &mut bodies.iter() {
+        if e == island {
+            body.set_active(true);
+            body.wake();
+        }
+    }
+}
+
+pub fn physics_system(
+    time: Res<Time>,
+    mut timestep: ResMut<FixedTimestep>,
+    mut world: ResMut<PhysicsWorld>,
+    mut handles: ResMut<BodyHandles>,
+    mut entered: ResMut<Events<SensorEntered>>,
+    mut exited: ResMut<Events<SensorExited>>,
+    mut joints: Query<&Joint>,
+    mut query: Query<(Entity, Mut<RigidBody>, Mut<Transform>)>,
+) {
+    let mut seen = HashSet::new();
+    for (e, body, _) in &mut query.iter() {
+        let mut body = body.clone();
+        // bodies moved by gameplay code since the last frame are not interpolated
+        if body.stepped != (body.position, body.rotation) {
+            body.previous = (body.position, body.rotation);
+        }
+        match handles.handle(e) {
+            Some(handle) => *world.get_mut(handle).unwrap() = body,
+            None => {
+                let handle = world.insert(body);
+                handles.handles.insert(e, handle);
+                handles.entities.insert(handle, e);
+            }
+        }
+        seen.insert(e);
+    }
+    let BodyHandles { handles, entities } = &mut *handles;
+    handles.retain(|e, &mut handle| {
+        if !seen.contains(e) {
+            world.remove(handle);
+            entities.remove(&handle);
+        }
+        seen.contains(e)
+    });
+    let joints = joints
+        .iter()
+        .iter()
+        .filter_map(|joint| joint.map(|e| handles.get(&e).copied()))
+        .collect();
+    world.set_joints(joints);
+
+    let steps = timestep.advance(time.delta.as_secs_f32());
+    for _ in 0..steps {
+        world.step(timestep.step());
+    }
+
+    for event in world.drain_sensor_events() {
+        match event {
+            SensorEvent::Entered { sensor, other } => entered.send(SensorEntered {
+                sensor: entities[&sensor],
+                other: entities[&other],
+            }),
+            SensorEvent::Exited { sensor, other } => exited.send(SensorExited {
+                sensor: entities[&sensor],
+                other: entities[&other],
+            }),
+        }
+    }
+
+    let alpha = timestep.alpha();
+    for (&e, &handle) in handles.iter() {
+        let b = world.get_mut(handle).unwrap();
+        b.accumulator = Vec2::zero();
+        b.stepped = (b.position, b.rotation);
+        *query.get_mut::<RigidBody>(e).unwrap() = b.clone();
+        let (position, rotation) = b.interpolated(alpha);
+        let mut transform = query.get_mut::<Transform>(e).unwrap();
+        transform.set_translation(Vec3::new(position.x(), 0.0, position.y()));
+        transform.set_rotation(Quat::from_rotation_y(rotation));
+    }
+}
+
+pub struct DebugDraw;
+
+pub fn debug_draw_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<Without<DebugDraw, (Entity, &RigidBody)>>,
+) {
+    for (e, body) in &mut query.iter() {
+        if !body.active {
+            continue;
+        }
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut indices = Vec::new();
+        for shape in body.shape.iter() {
+            let hull = shape.hull(Vec2::zero(), 0.0);
+            // round shapes are drawn by sampling their outline
+            let outline = if hull.radius == 0.0 {
+                hull.points.to_vec()
+            } else {
+                (0..32)
+                    .map(|i| {
+                        let dir =
+                            Mat2::from_angle(i as f32 / 32.0 * 2.0 * PI) * Vec2::new(1.0, 0.0);
+                        let support = hull
+                            .points
+                            .iter()
+                            .copied()
+                            .max_by(|p, q| p.dot(dir).partial_cmp(&q.dot(dir)).unwrap())
+                            .unwrap();
+                        support + dir * hull.radius
+                    })
+                    .collect()
+            };
+            let c = positions.len() as u32;
+            let n = outline.len() as u32;
+            for (i, point) in outline.into_iter().enumerate() {
+                positions.push([point.x(), 4.0, point.y()]);
+                normals.push([0.0, 1.0, 0.0]);
+                uvs.push([0.0, 0.0]);
+                indices.extend(&[c + i as u32, c + (i as u32 + 1) % n]);
+            }
+        }
+        let attributes = vec![
+            VertexAttribute::position(positions),
+            VertexAttribute::normal(normals),
+            VertexAttribute::uv(uvs),
+        ];
+        let mesh = Mesh {
+            primitive_topology: PrimitiveTopology::LineList,
+            attributes,
+            indices: Some(indices),
+        };
+        let handle = meshes.add(mesh);
+        let color = if body.sensor {
+            Color::rgba(0.0, 0.0, 1.0, 0.0)
+        } else {
+            Color::rgba(1.0, 0.0, 0.0, 0.0)
+        };
+        commands
+            .spawn(PbrComponents {
+                mesh: handle,
+                material: materials.add(color.into()),
+                ..Default::default()
+            })
+            .with(Parent(e))
+            .insert_one(e, DebugDraw);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+    use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+    fn body(position: Vec2, rotation: f32) -> RigidBody {
+        RigidBody::new(Status::Dynamic, 1.0, 0.0)
+            .position(position)
+            .rotation(rotation)
+    }
+
+    fn square(position: Vec2, rotation: f32) -> RigidBody {
+        body(position, rotation).shape(Vec2::new(-0.5, -0.5), 1.0, 1.0)
+    }
+
+    fn contacts(body1: &RigidBody, body2: &RigidBody) -> Vec<(Vec2, f32)> {
+        collide(body1, body2).collect()
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotated_boxes_depth() {
+        // face to face along the diagonal, their centers 0.6 * sqrt(2) apart
+        let a = square(Vec2::zero(), FRAC_PI_4);
+        let b = square(Vec2::new(0.6, 0.6), FRAC_PI_4);
+        let contacts = contacts(&a, &b);
+        assert_eq!(contacts.len(), 1);
+        let (normal, depth) = contacts[0];
+        assert_close(normal, Vec2::new(1.0, 1.0) / SQRT_2);
+        assert!((depth - (1.0 - 0.6 * SQRT_2)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn normal_points_from_body1_to_body2() {
+        let a = square(Vec2::zero(), FRAC_PI_4);
+        let b = square(Vec2::new(0.6, 0.6), FRAC_PI_4);
+        let (ab, _) = contacts(&a, &b)[0];
+        let (ba, _) = contacts(&b, &a)[0];
+        assert!(ab.dot(b.position - a.position) > 0.0);
+        assert_close(ba, -ab);
+
+        let a = square(Vec2::zero(), 0.3);
+        let b = square(Vec2::new(-0.9, 0.2), -0.2);
+        let (normal, _) = contacts(&a, &b)[0];
+        assert!(normal.dot(b.position - a.position) > 0.0);
+    }
+
+    #[test]
+    fn aligned_fast_path_matches_sat() {
+        let cases = [
+            (Vec2::new(0.8, 0.3), 1.0, 1.0),
+            (Vec2::new(-0.3, 0.9), 1.0, 1.0),
+            (Vec2::new(0.5, -0.2), 2.0, 0.5),
+            (Vec2::new(-1.2, -0.4), 2.0, 1.5),
+        ];
+        for &(position, width, height) in &cases {
+            let a = square(Vec2::zero(), 0.0);
+            let b =
+                body(position, 0.0).shape(Vec2::new(-width / 2.0, -height / 2.0), width, height);
+            let (fast_normal, fast_depth) = contacts(&a, &b)[0];
+            let (normal, depth) = contact(
+                &a.shape[0].hull(a.position, a.rotation),
+                &b.shape[0].hull(b.position, b.rotation),
+            )
+            .unwrap();
+            assert_close(fast_normal, normal);
+            assert!((fast_depth - depth).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn circle_circle() {
+        let a = body(Vec2::zero(), 0.0).circle(Vec2::zero(), 0.5);
+        let b = body(Vec2::new(0.8, 0.0), 0.0).circle(Vec2::zero(), 0.5);
+        let (normal, depth) = contacts(&a, &b)[0];
+        assert_close(normal, Vec2::new(1.0, 0.0));
+        assert!((depth - 0.2).abs() < 1e-5);
+
+        let b = b.position(Vec2::new(0.0, 1.1));
+        assert!(contacts(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn circle_rect_corner() {
+        let a = square(Vec2::zero(), 0.0);
+        let b = body(Vec2::new(0.8, 0.8), 0.0).circle(Vec2::zero(), 0.5);
+        let (normal, depth) = contacts(&a, &b)[0];
+        assert_close(normal, Vec2::new(1.0, 1.0) / SQRT_2);
+        assert!((depth - (0.5 - 0.3 * SQRT_2)).abs() < 1e-5);
+
+        // just past the corner's reach, though the aabbs overlap
+        let b = b.position(Vec2::new(0.9, 0.9));
+        assert!(contacts(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn capsule_rect() {
+        let a = square(Vec2::zero(), 0.0);
+        let b =
+            body(Vec2::new(0.0, 0.7), 0.0).capsule(Vec2::new(-1.0, 0.0), Vec2::new(1.0, 0.0), 0.25);
+        let (normal, depth) = contacts(&a, &b)[0];
+        assert_close(normal, Vec2::new(0.0, 1.0));
+        assert!((depth - 0.05).abs() < 1e-5);
+
+        // touching the corner with the rounded end of its segment
+        let b = b.position(Vec2::new(1.6, 0.6));
+        let (normal, depth) = contacts(&a, &b)[0];
+        assert_close(normal, Vec2::new(1.0, 1.0) / SQRT_2);
+        assert!((depth - (0.25 - 0.1 * SQRT_2)).abs() < 1e-5);
+    }
+
+    // a crowd of every kind of body, some of them filtered out by status,
+    // layers or being inactive
+    fn crowd(n: usize, seed: u64) -> Vec<RigidBody> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let side = (n as f32).sqrt() * 2.0;
+        (0..n)
+            .map(|_| {
+                let status = match rng.gen_range(0, 3) {
+                    0 => Status::Static,
+                    1 => Status::Dynamic,
+                    _ => Status::Semikinematic,
+                };
+                let position = Vec2::new(rng.gen_range(0.0, side), rng.gen_range(0.0, side));
+                let (width, height) = (rng.gen_range(0.1, 1.5), rng.gen_range(0.1, 1.5));
+                let mut body = body(position, rng.gen_range(0.0, PI));
+                body.status = status;
+                let mut body = if rng.gen_bool(0.5) {
+                    body.shape(Vec2::new(-width, -height) / 2.0, width, height)
+                } else {
+                    body.circle(Vec2::zero(), width / 2.0)
+                };
+                if rng.gen_bool(0.2) {
+                    body = body.layers(LAYER_PLAYER, LAYER_DOOR);
+                }
+                body.set_active(rng.gen_bool(0.9));
+                body
+            })
+            .collect()
+    }
+
+    #[test]
+    fn broad_phase_matches_naive_pairs() {
+        for seed in 0..20 {
+            for &n in &[0, 1, 2, 10, 100, 500] {
+                let bodies = crowd(n, seed);
+                assert_eq!(
+                    broad_phase(&bodies),
+                    naive_pairs(&bodies),
+                    "{} bodies from seed {}",
+                    n,
+                    seed
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,628 @@
+use bevy::math::*;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BodyHandle {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    // points from a to b
+    pub normal: Vec2,
+    pub depth: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorEvent {
+    Entered {
+        sensor: BodyHandle,
+        other: BodyHandle,
+    },
+    Exited {
+        sensor: BodyHandle,
+        other: BodyHandle,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub body: BodyHandle,
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    body: Option<RigidBody>,
+}
+
+// owns bodies without knowing anything about entities, physics_system copies
+// them in and out every frame
+#[derive(Debug, Clone, Default)]
+pub struct PhysicsWorld {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    joints: Vec<Joint<BodyHandle>>,
+    contacts: Vec<Contact>,
+    // (sensor, other) pairs that overlapped after the last step
+    overlaps: Vec<(BodyHandle, BodyHandle)>,
+    sensor_events: Vec<SensorEvent>,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, body: RigidBody) -> BodyHandle {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.body = Some(body);
+                BodyHandle {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    body: Some(body),
+                });
+                BodyHandle {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let body = slot.body.take()?;
+        slot.generation += 1;
+        self.free.push(handle.index);
+        self.overlaps
+            .retain(|&(sensor, other)| sensor != handle && other != handle);
+        Some(body)
+    }
+
+    pub fn get(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.body.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.body.as_mut())
+    }
+
+    pub fn handles<'a>(&'a self) -> impl Iterator<Item = BodyHandle> + 'a {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.body.as_ref().map(|_| BodyHandle {
+                index: index as u32,
+                generation: slot.generation,
+            })
+        })
+    }
+
+    pub fn add_joint(&mut self, joint: Joint<BodyHandle>) {
+        self.joints.push(joint);
+    }
+
+    pub fn set_joints(&mut self, joints: Vec<Joint<BodyHandle>>) {
+        self.joints = joints;
+    }
+
+    pub fn joints(&self) -> &[Joint<BodyHandle>] {
+        &self.joints
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // solid contacts found by the last step
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    // sensor events of every step since the last drain
+    pub fn drain_sensor_events(&mut self) -> impl Iterator<Item = SensorEvent> + '_ {
+        self.sensor_events.drain(..)
+    }
+
+    pub fn is_overlapping(&self, sensor: BodyHandle, other: BodyHandle) -> bool {
+        self.overlaps.contains(&(sensor, other))
+    }
+
+    // queries only see solid bodies, sensors are reported through events
+    fn queryable<'a>(
+        &'a self,
+        mask: u32,
+    ) -> impl Iterator<Item = (BodyHandle, &'a RigidBody)> + 'a {
+        self.handles()
+            .map(move |handle| (handle, self.get(handle).unwrap()))
+            .filter(move |(_, body)| body.active && !body.sensor && body.category & mask != 0)
+    }
+
+    // first body along the ray whose category is in mask, dir does not need
+    // to be normalized
+    pub fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32, mask: u32) -> Option<RayHit> {
+        if dir.length_squared() == 0.0 {
+            return None;
+        }
+        let dir = dir.normalize();
+        let mut best: Option<RayHit> = None;
+        for (handle, body) in self.queryable(mask) {
+            for shape in body.shape.iter() {
+                let hull = shape.hull(body.position, body.rotation);
+                if let Some((distance, normal)) = hull.raycast(origin, dir) {
+                    let closer = best.is_none_or(|hit| distance < hit.distance);
+                    if distance <= max_dist && closer {
+                        best = Some(RayHit {
+                            body: handle,
+                            point: origin + dir * distance,
+                            normal,
+                            distance,
+                        });
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    // every body whose category is in mask and that overlaps the box
+    pub fn overlap_box(
+        &self,
+        center: Vec2,
+        half_extents: Vec2,
+        rotation: f32,
+        mask: u32,
+    ) -> Vec<BodyHandle> {
+        let query = RigidBody::new(Status::Static, INF_MASS, 0.0)
+            .shape(
+                -half_extents,
+                half_extents.x() * 2.0,
+                half_extents.y() * 2.0,
+            )
+            .position(center)
+            .rotation(rotation);
+        let aabb = query.aabb().unwrap();
+        self.queryable(mask)
+            .filter(|(_, body)| body.aabb().is_some_and(|other| aabb.overlaps(&other)))
+            .filter(|(_, body)| collide(&query, body).next().is_some())
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    pub fn step(&mut self, delta_time: f32) {
+        let handles = self.handles().collect::<Vec<_>>();
+        let mut bodies = handles
+            .iter()
+            .map(|&handle| self.get(handle).unwrap().clone())
+            .collect::<Vec<_>>();
+
+        for i in 0..bodies.len() {
+            let body = &mut bodies[i];
+            body.previous = (body.position, body.rotation);
+            if !body.active {
+                continue;
+            }
+            if body.sleeping {
+                // gameplay code pushed it
+                if body.accumulator.length_squared() > 0.0 || !body.is_resting() {
+                    body.wake();
+                } else {
+                    continue;
+                }
+            }
+            body.rotation += body.angular_velocity * delta_time;
+            let motion = body.velocity * delta_time;
+            if needs_sweep(body, motion) {
+                bodies[i].position = sweep(&bodies, i, motion);
+            } else {
+                body.position += motion;
+            }
+        }
+
+        let index = handles
+            .iter()
+            .enumerate()
+            .map(|(i, &handle)| (handle, i))
+            .collect::<HashMap<_, _>>();
+        for joint in &self.joints {
+            let (body1, body2) = joint.bodies();
+            let (i, j) = match (index.get(&body1), index.get(&body2)) {
+                (Some(&i), Some(&j)) if i != j => (i, j),
+                _ => continue,
+            };
+            // fixed joints also carry inactive bodies along, like room sensors
+            // following their room
+            let fixed = matches!(joint.kind(), JointKind::Fixed { .. });
+            if !(fixed || bodies[i].active && bodies[j].active) {
+                continue;
+            }
+            if !fixed && !wake_pair(&mut bodies, i, j) {
+                continue;
+            }
+            let (body1, body2) = if i < j {
+                let (left, right) = bodies.split_at_mut(j);
+                (&mut left[i], &mut right[0])
+            } else {
+                let (left, right) = bodies.split_at_mut(i);
+                (&mut right[0], &mut left[j])
+            };
+            joint::solve(joint.kind(), body1, body2, delta_time);
+        }
+
+        let mut contacts = Vec::new();
+        for (i, j) in broad_phase(&bodies) {
+            contacts.extend(collide(&bodies[i], &bodies[j]).map(|contact| (i, j, contact)));
+        }
+
+        self.contacts.clear();
+        let mut overlaps = Vec::new();
+        let mut corrections = vec![Vec2::zero(); bodies.len()];
+        for (i, j, (normal, depth)) in contacts {
+            let (a, b) = (handles[i], handles[j]);
+            if bodies[i].sensor || bodies[j].sensor {
+                // a pair of sensors overlaps from both sides
+                for &(sensor, k, other) in &[(a, i, b), (b, j, a)] {
+                    if bodies[k].sensor && !overlaps.contains(&(sensor, other)) {
+                        overlaps.push((sensor, other));
+                    }
+                }
+                continue;
+            }
+            self.contacts.push(Contact {
+                a,
+                b,
+                normal,
+                depth,
+            });
+            if !wake_pair(&mut bodies, i, j) {
+                continue;
+            }
+            resolve(&mut bodies, &mut corrections, i, j, normal, depth);
+        }
+
+        for &(sensor, other) in &self.overlaps {
+            if !overlaps.contains(&(sensor, other)) {
+                self.sensor_events
+                    .push(SensorEvent::Exited { sensor, other });
+            }
+        }
+        for &(sensor, other) in &overlaps {
+            if !self.overlaps.contains(&(sensor, other)) {
+                self.sensor_events
+                    .push(SensorEvent::Entered { sensor, other });
+            }
+        }
+        self.overlaps = overlaps;
+
+        for (body, correction) in bodies.iter_mut().zip(corrections) {
+            if !body.active || body.sleeping {
+                continue;
+            }
+            body.position += correction;
+            body.velocity += body.accumulator * delta_time;
+            body.velocity *= body.material.damping_over(delta_time);
+            if body.status == Status::Dynamic && body.is_resting() {
+                body.rest_time += delta_time;
+                if body.rest_time >= SLEEP_TIME {
+                    body.sleep();
+                }
+            } else {
+                body.rest_time = 0.0;
+            }
+        }
+
+        for (&handle, body) in handles.iter().zip(bodies) {
+            *self.get_mut(handle).unwrap() = body;
+        }
+    }
+}
+
+// wakes a sleeping body up when the other one of the pair can, returns false
+// if the pair is left asleep and needs no solving
+fn wake_pair(bodies: &mut [RigidBody], i: usize, j: usize) -> bool {
+    match (bodies[i].sleeping, bodies[j].sleeping) {
+        (false, false) => true,
+        (true, true) => false,
+        (true, false) if bodies[j].wakes_others() => {
+            bodies[i].wake();
+            true
+        }
+        (false, true) if bodies[i].wakes_others() => {
+            bodies[j].wake();
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // keeps its velocity unless something stops it
+    fn mover(position: Vec2, velocity: Vec2) -> RigidBody {
+        let mut body = RigidBody::new(Status::Semikinematic, 1.0, 0.0)
+            .material(PhysicsMaterial::default().damping(0.0))
+            .shape(Vec2::new(-0.1, -0.1), 0.2, 0.2)
+            .position(position);
+        body.velocity = velocity;
+        body
+    }
+
+    fn wall() -> RigidBody {
+        RigidBody::new(Status::Static, INF_MASS, 0.0)
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        let mut world = PhysicsWorld::new();
+        let body = world.insert(mover(Vec2::new(0.39, 0.0), Vec2::new(1.0, 1.0)));
+        world.insert(wall().shape(Vec2::new(0.5, -5.0), 0.1, 10.0));
+
+        world.step(DT);
+
+        assert_eq!(world.contacts().len(), 1);
+        let body = world.get(body).unwrap();
+        assert!(body.position.x() <= 0.4 + 1e-4);
+        assert!(body.velocity.x().abs() < 1e-6);
+        assert!((body.velocity.y() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn passes_through_doorway() {
+        let mut world = PhysicsWorld::new();
+        let body = world.insert(mover(Vec2::new(0.0, -1.0), Vec2::new(0.0, 3.0)));
+        world.insert(wall().shape(Vec2::new(-2.0, 0.0), 1.5, 0.1).shape(
+            Vec2::new(0.5, 0.0),
+            1.5,
+            0.1,
+        ));
+
+        for _ in 0..60 {
+            world.step(DT);
+            assert!(world.contacts().is_empty());
+        }
+
+        let body = world.get(body).unwrap();
+        assert!(body.position.y() > 1.0);
+        assert!(body.position.x().abs() < 1e-6);
+        assert!((body.velocity.y() - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sensor_events_once_per_overlap() {
+        let mut world = PhysicsWorld::new();
+        let other = world.insert(mover(Vec2::new(-2.0, 0.0), Vec2::new(3.0, 0.0)));
+        let mut body = wall().shape(Vec2::new(-0.5, -0.5), 1.0, 1.0);
+        body.set_sensor(true);
+        let sensor = world.insert(body);
+
+        let mut events = Vec::new();
+        for _ in 0..120 {
+            world.step(DT);
+            events.extend(world.drain_sensor_events());
+        }
+
+        assert_eq!(
+            events,
+            vec![
+                SensorEvent::Entered { sensor, other },
+                SensorEvent::Exited { sensor, other },
+            ]
+        );
+        assert!(world.contacts().is_empty());
+        assert!(world.get(other).unwrap().position.x() > 1.0);
+    }
+
+    // a dynamic box moving five wall thicknesses per step
+    fn bullet(velocity: Vec2) -> RigidBody {
+        let mut body = RigidBody::new(Status::Dynamic, 1.0, 0.0)
+            .material(PhysicsMaterial::default().damping(0.0))
+            .shape(Vec2::new(-0.1, -0.1), 0.2, 0.2);
+        body.velocity = velocity;
+        body
+    }
+
+    #[test]
+    fn fast_body_stops_at_thin_wall() {
+        let mut world = PhysicsWorld::new();
+        let body = world.insert(bullet(Vec2::new(30.0, 0.0)));
+        world.insert(wall().shape(Vec2::new(1.0, -5.0), 0.1, 10.0));
+
+        for _ in 0..30 {
+            world.step(DT);
+            let x = world.get(body).unwrap().position.x();
+            assert!(x + 0.1 <= 1.0, "passed into the wall at {}", x);
+        }
+
+        let body = world.get(body).unwrap();
+        assert!(body.position.x() + 0.1 > 1.0 - 0.01);
+        assert!(body.position.y().abs() < 1e-6);
+    }
+
+    #[test]
+    fn fast_body_slides_along_thin_wall() {
+        let mut world = PhysicsWorld::new();
+        let body = world.insert(bullet(Vec2::new(30.0, 30.0)));
+        world.insert(wall().shape(Vec2::new(1.0, -5.0), 0.1, 20.0));
+
+        for _ in 0..10 {
+            world.step(DT);
+            let x = world.get(body).unwrap().position.x();
+            assert!(x + 0.1 <= 1.0, "passed into the wall at {}", x);
+        }
+
+        // ten steps of half a meter along y, the wall takes none of it
+        let body = world.get(body).unwrap();
+        assert!((body.position.y() - 5.0).abs() < 1e-3);
+    }
+
+    fn prop(position: Vec2) -> RigidBody {
+        RigidBody::new(Status::Dynamic, 1.0, 0.0)
+            .shape(Vec2::new(-0.1, -0.1), 0.2, 0.2)
+            .position(position)
+    }
+
+    fn steps(time: f32) -> usize {
+        (time / DT).ceil() as usize
+    }
+
+    #[test]
+    fn resting_body_sleeps() {
+        let mut world = PhysicsWorld::new();
+        let body = world.insert(prop(Vec2::zero()));
+
+        for _ in 0..steps(SLEEP_TIME) - 2 {
+            world.step(DT);
+        }
+        assert!(!world.get(body).unwrap().is_sleeping());
+
+        for _ in 0..4 {
+            world.step(DT);
+        }
+        assert!(world.get(body).unwrap().is_sleeping());
+    }
+
+    #[test]
+    fn moving_body_wakes_sleeper() {
+        let mut world = PhysicsWorld::new();
+        let sleeper = world.insert(prop(Vec2::zero()));
+        for _ in 0..steps(SLEEP_TIME) + 2 {
+            world.step(DT);
+        }
+        assert!(world.get(sleeper).unwrap().is_sleeping());
+
+        world.insert(bullet(Vec2::new(3.0, 0.0)).position(Vec2::new(-1.0, 0.0)));
+        let mut touched = false;
+        for _ in 0..60 {
+            world.step(DT);
+            if !world.contacts().is_empty() {
+                touched = true;
+                break;
+            }
+        }
+
+        assert!(touched);
+        let sleeper = world.get(sleeper).unwrap();
+        assert!(!sleeper.is_sleeping());
+        assert!(sleeper.velocity.x() > 0.0);
+    }
+
+    #[test]
+    fn static_wall_lets_prop_sleep() {
+        let mut world = PhysicsWorld::new();
+        // sinks into the wall by less than the slop, so nothing pushes it out
+        let body = world.insert(prop(Vec2::new(0.405, 0.0)));
+        world.insert(wall().shape(Vec2::new(0.5, -5.0), 0.1, 10.0));
+
+        for _ in 0..steps(SLEEP_TIME) * 2 {
+            world.step(DT);
+        }
+
+        assert_eq!(world.contacts().len(), 1);
+        assert!(world.get(body).unwrap().is_sleeping());
+    }
+
+    fn post(x: f32) -> RigidBody {
+        wall().shape(Vec2::new(x, -0.5), 0.5, 1.0)
+    }
+
+    #[test]
+    fn raycast_nearest_hit() {
+        let mut world = PhysicsWorld::new();
+        world.insert(post(4.0));
+        let near = world.insert(post(2.0));
+
+        let hit = world
+            .raycast(Vec2::zero(), Vec2::new(3.0, 0.0), 10.0, LAYER_ALL)
+            .unwrap();
+        assert_eq!(hit.body, near);
+        assert!((hit.distance - 2.0).abs() < 1e-5);
+        assert!((hit.point - Vec2::new(2.0, 0.0)).length() < 1e-5);
+        assert!((hit.normal - Vec2::new(-1.0, 0.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn raycast_mask() {
+        let mut world = PhysicsWorld::new();
+        world.insert(post(2.0).layers(LAYER_DOOR, LAYER_ALL));
+        let far = world.insert(post(4.0));
+
+        let hit = world.raycast(Vec2::zero(), Vec2::new(1.0, 0.0), 10.0, LAYER_DEFAULT);
+        assert_eq!(hit.map(|hit| hit.body), Some(far));
+        let hit = world.raycast(Vec2::zero(), Vec2::new(1.0, 0.0), 10.0, LAYER_PLAYER);
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn raycast_skips_sensors_and_removed() {
+        let mut world = PhysicsWorld::new();
+        let mut sensor = post(1.0);
+        sensor.set_sensor(true);
+        world.insert(sensor);
+        let removed = world.insert(post(2.0));
+        let far = world.insert(post(4.0));
+        world.remove(removed);
+
+        let hit = world.raycast(Vec2::zero(), Vec2::new(1.0, 0.0), 10.0, LAYER_ALL);
+        assert_eq!(hit.map(|hit| hit.body), Some(far));
+
+        // the removed body's slot is reused under a new handle
+        let reused = world.insert(post(3.0));
+        let hit = world.raycast(Vec2::zero(), Vec2::new(1.0, 0.0), 10.0, LAYER_ALL);
+        assert_eq!(hit.map(|hit| hit.body), Some(reused));
+        assert_ne!(reused, removed);
+    }
+
+    #[test]
+    fn raycast_max_dist() {
+        let mut world = PhysicsWorld::new();
+        world.insert(post(2.0));
+
+        let dir = Vec2::new(1.0, 0.0);
+        assert!(world.raycast(Vec2::zero(), dir, 1.5, LAYER_ALL).is_none());
+        assert!(world.raycast(Vec2::zero(), dir, 2.5, LAYER_ALL).is_some());
+    }
+
+    #[test]
+    fn overlap_box_filters() {
+        let mut world = PhysicsWorld::new();
+        let hit = world.insert(post(0.0));
+        world.insert(post(0.5).layers(LAYER_DOOR, LAYER_ALL));
+        let mut sensor = post(0.0);
+        sensor.set_sensor(true);
+        world.insert(sensor);
+        world.insert(post(5.0));
+
+        let found = world.overlap_box(Vec2::new(0.5, 0.0), Vec2::splat(0.5), 0.0, LAYER_DEFAULT);
+        assert_eq!(found, vec![hit]);
+    }
+}
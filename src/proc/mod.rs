@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::f32::consts::PI;
 use std::ops::Not;
 
@@ -5,19 +6,69 @@ use bevy::prelude::*;
 use bevy::render::mesh::*;
 use hashbrown::{HashMap, HashSet};
 use rand::distributions::{Distribution, Standard};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::phys::*;
 use crate::room::*;
 
 pub mod walls;
 
+// radians per second
+pub const DOOR_SPEED: f32 = 4.0;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Connection {
     pub this: Entity,
+    pub door: Door,
     pub room: Entity,
     pub sensor: Entity,
     pub open: bool,
+    // position and rotation of the closed door in its room
+    pub closed: (Vec2, f32),
+}
+
+// where a door is hinged, in the door's own coordinates
+fn door_hinge() -> Vec2 {
+    Vec2::new(0.5, 0.0)
+}
+
+impl Connection {
+    pub fn hinge(&self, door: Entity) -> Joint {
+        let (position, rotation) = self.closed;
+        let anchor = position + rotate(door_hinge(), rotation);
+        Joint::hinge(self.this, door, anchor, door_hinge())
+            .reference(rotation)
+            .limits(0.0, 90.0_f32.to_radians())
+            .motor(-DOOR_SPEED)
+    }
+
+    pub fn pose(&self, open: bool) -> (Vec2, f32) {
+        let (position, rotation) = self.closed;
+        if !open {
+            return self.closed;
+        }
+        let anchor = position + rotate(door_hinge(), rotation);
+        let rotation = rotation + 90.0_f32.to_radians();
+        (anchor - rotate(door_hinge(), rotation), rotation)
+    }
+
+    // swings the door open or closed on its hinge
+    pub fn set_open(&mut self, body: &mut RigidBody, joint: &mut Joint, open: bool) {
+        self.open = open;
+        body.set_sensor(open);
+        joint.set_motor(Some(if open { DOOR_SPEED } else { -DOOR_SPEED }));
+    }
+
+    // puts the door straight into place, for when nobody is watching
+    pub fn snap_open(&mut self, body: &mut RigidBody, joint: &mut Joint, open: bool) {
+        self.set_open(body, joint, open);
+        let (position, rotation) = self.pose(open);
+        body.position = position;
+        body.rotation = rotation;
+        body.angular_velocity = 0.0;
+    }
 }
 
 #[derive(Bundle)]
@@ -36,7 +87,7 @@ pub struct RoomSensorBundle {
     body: RigidBody,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Door {
     // -z
     North,
@@ -61,6 +112,8 @@ impl Distribution<Door> for Standard {
 }
 
 impl Door {
+    pub const ALL: [Door; 4] = [Door::North, Door::South, Door::East, Door::West];
+
     pub fn rotation(self, other: Self) -> f32 {
         use Door::East as E;
         use Door::North as N;
@@ -105,15 +158,26 @@ impl Not for Door {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PropPrototype {
     pub name: String,
     pub position: Vec2,
     pub rotation: f32,
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default = "prop_mass")]
+    pub mass: f32,
+    #[serde(default)]
+    pub material: Option<PhysicsMaterial>,
 }
 
-#[derive(Debug, Clone)]
+fn prop_mass() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RoomPrototype {
+    pub id: String,
     pub name: String,
     pub description: String,
     pub color: Color,
@@ -121,23 +185,33 @@ pub struct RoomPrototype {
     pub depth: f32,
     pub height: f32,
     pub doors: HashSet<Door>,
+    #[serde(default)]
     pub edges: Vec<EdgePrototype>,
+    #[serde(default)]
     pub props: Vec<PropPrototype>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EdgePrototype {
-    pub index: usize,
+    pub room: String,
     pub from: Door,
     pub to: Door,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LevelPrototype {
-    pub start: usize,
+    pub start: String,
+    #[serde(default)]
+    pub goal: Option<String>,
     pub rooms: Vec<RoomPrototype>,
 }
 
+impl LevelPrototype {
+    pub fn index(&self, id: &str) -> Option<usize> {
+        self.rooms.iter().position(|room| room.id == id)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DcEdge {
     index: usize,
@@ -241,37 +315,22 @@ pub fn spawn(
             let handle = assets
                 .load_sync(meshes, format!("assets/mesh/prop_{}.gltf", prop.name))
                 .unwrap();
-            let mesh = meshes.get(&handle).unwrap();
-            let mut min = Vec2::zero();
-            let mut max = Vec2::zero();
-            for attr in &mesh.attributes {
-                if attr.name == VertexAttribute::POSITION {
-                    match &attr.values {
-                        VertexAttributeValues::Float3(values) => {
-                            for positions in values {
-                                let x = min.x().min(positions[0]);
-                                *min.x_mut() = x;
-                                let y = min.y().min(positions[2]);
-                                *min.y_mut() = y;
-
-                                let x = max.x().max(positions[0]);
-                                *max.x_mut() = x;
-                                let y = max.y().max(positions[2]);
-                                *max.y_mut() = y;
-                            }
-                        }
-                        _ => unimplemented!(),
-                    }
-                    break;
-                }
-            }
-            let width = max.x() - min.x();
-            let height = max.y() - min.y();
-            let offset = min;
-            let mut body = RigidBody::new(Status::Static, 1.0, 0.5)
-                .shape(offset, width, height)
-                .position(prop.position)
-                .rotation(prop.rotation);
+            let aabb = footprint(meshes.get(&handle).unwrap());
+            let width = aabb.max.x() - aabb.min.x();
+            let height = aabb.max.y() - aabb.min.y();
+            let offset = aabb.min;
+            let body = RigidBody::new(prop.status, prop.mass, 0.5);
+            let body = match prop.material {
+                Some(material) => body.material(material),
+                None => body,
+            };
+            // round props stay inside the footprint that placement checks
+            let body = if ROUND_PROPS.contains(&prop.name.as_str()) {
+                body.circle((aabb.min + aabb.max) * 0.5, width.min(height) / 2.0)
+            } else {
+                body.shape(offset, width, height)
+            };
+            let mut body = body.position(prop.position).rotation(prop.rotation);
             body.set_active(false);
             commands
                 .spawn(PbrComponents {
@@ -292,6 +351,7 @@ pub fn spawn(
         commands
             .spawn(RoomBundle {
                 marker: RoomMarker,
+                id: RoomId(room.id.clone()),
                 name: Name::new(room.name.clone(), room.description.clone()),
                 body,
                 props: props.clone(),
@@ -311,18 +371,18 @@ pub fn spawn(
             });
 
         let current = current.unwrap();
+        commands.insert_one(current, Island(current));
 
         for &e in &props.vec {
             commands.insert_one(e, Parent(current));
+            commands.insert_one(e, Island(current));
         }
 
         let w = room.width - 1.5;
         let h = room.depth - 1.5;
-        let mut body = RigidBody::new(Status::Static, INF_MASS, 0.5).shape(
-            Vec2::new(-w / 2.0, -h / 2.0),
-            w,
-            h,
-        );
+        let mut body = RigidBody::new(Status::Static, INF_MASS, 0.5)
+            .shape(Vec2::new(-w / 2.0, -h / 2.0), w, h)
+            .layers(LAYER_ROOM_SENSOR, LAYER_PLAYER);
         body.set_active(false);
         body.set_sensor(true);
         commands
@@ -342,14 +402,14 @@ pub fn spawn(
         sensors.insert(current, sensor);
     }
 
-    let current = rooms[&level.start];
+    let current = rooms[&level.index(&level.start).unwrap()];
     commands.insert_resource(CurrentRoom { entity: current });
 
     let mut dcg = Vec::new();
 
     for &(i, a, ref con) in &edges {
         for (index, edge) in con.iter().enumerate() {
-            let (j, b, _) = edges[edge.index];
+            let (j, b, _) = edges[level.index(&edge.room).unwrap()];
             dcg.push(DcEdge { index, i, j, a, b });
         }
     }
@@ -412,7 +472,7 @@ pub fn spawn(
             let mut j = None;
             for edge in &room.edges {
                 if edge.from == door {
-                    j = Some(edge.index);
+                    j = level.index(&edge.room);
                 }
             }
 
@@ -439,8 +499,18 @@ pub fn spawn(
             let mut body = RigidBody::new(Status::Static, INF_MASS, 0.5)
                 .position(position)
                 .rotation(rotation)
-                .shape(Vec2::new(-0.5, 0.0), width, height);
+                .shape(Vec2::new(-0.5, 0.0), width, height)
+                .layers(LAYER_DOOR, LAYER_ALL);
             body.set_active(false);
+            let connection = Connection {
+                this: current,
+                door,
+                room: conn,
+                sensor,
+                open: false,
+                closed: (position, rotation),
+            };
+            let mut entity = None;
             commands
                 .spawn(PbrComponents {
                     draw: Draw {
@@ -451,48 +521,105 @@ pub fn spawn(
                     material: materials.add(room.color.into()),
                     ..Default::default()
                 })
-                .with_bundle(DoorBundle {
-                    connection: Connection {
-                        this: current,
-                        room: conn,
-                        sensor,
-                        open: false,
-                    },
-                })
+                .with_bundle(DoorBundle { connection })
                 .with(Parent(current))
+                .with(Island(current))
                 .with(body)
-                .for_current_entity(|e| doorset.push(e));
+                .for_current_entity(|e| entity = Some(e));
+            let entity = entity.unwrap();
+            commands.insert_one(entity, connection.hinge(entity));
+            doorset.push(entity);
         }
 
         commands.insert_one(current, DoorSet { vec: doorset });
     }
 }
 
+pub fn footprint(mesh: &Mesh) -> Aabb {
+    let mut min = Vec2::zero();
+    let mut max = Vec2::zero();
+    for attr in &mesh.attributes {
+        if attr.name == VertexAttribute::POSITION {
+            match &attr.values {
+                VertexAttributeValues::Float3(values) => {
+                    for positions in values {
+                        let x = min.x().min(positions[0]);
+                        *min.x_mut() = x;
+                        let y = min.y().min(positions[2]);
+                        *min.y_mut() = y;
+
+                        let x = max.x().max(positions[0]);
+                        *max.x_mut() = x;
+                        let y = max.y().max(positions[2]);
+                        *max.y_mut() = y;
+                    }
+                }
+                _ => unimplemented!(),
+            }
+            break;
+        }
+    }
+    Aabb::new(min, max)
+}
+
+// footprint of every prop used in the level, by name
+pub fn footprints(
+    assets: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    level: &LevelPrototype,
+) -> HashMap<String, Aabb> {
+    let mut footprints = HashMap::new();
+    for prop in level.rooms.iter().flat_map(|room| &room.props) {
+        if footprints.contains_key(&prop.name) {
+            continue;
+        }
+        let handle = assets
+            .load_sync(meshes, format!("assets/mesh/prop_{}.gltf", prop.name))
+            .unwrap();
+        let footprint = footprint(meshes.get(&handle).unwrap());
+        footprints.insert(prop.name.clone(), footprint);
+    }
+    footprints
+}
+
+pub const ROUND_PROPS: &[&str] = &["flower_table", "lamp", "mobius"];
+pub const DYNAMIC_PROPS: &[&str] = &["chair", "rev_chair"];
+pub const DYNAMIC_PROP_MASS: f32 = 5.0;
+pub const PLACEMENT_ATTEMPTS: usize = 32;
+pub const CORRIDOR_WIDTH: f32 = 1.4;
+
 #[derive(Default)]
 pub struct Parameters {
+    pub seed: u64,
     pub size: usize,
     pub min_size: f32,
     pub max_size: f32,
     pub min_height: f32,
     pub max_height: f32,
     pub clone_probability: f32,
+    pub goal: bool,
     pub min_props: usize,
     pub max_props: usize,
-    pub props: Vec<String>,
+    pub props: Vec<(String, Aabb)>,
 }
 
 pub fn generate(params: &Parameters) -> LevelPrototype {
+    assert!(params.size > 0, "cannot generate a level without rooms");
+    let mut rng = ChaCha8Rng::seed_from_u64(params.seed);
+    generate_with_rng(params, &mut rng)
+}
+
+pub fn generate_with_rng<R: Rng + ?Sized>(params: &Parameters, rng: &mut R) -> LevelPrototype {
     let mut size = params.size;
 
-    let start = 0;
     let mut rooms = Vec::new();
 
     while size > 0 {
-        let width = rand::random::<f32>() * (params.max_size - params.min_size) + params.min_size;
-        let depth = rand::random::<f32>() * (params.max_size - params.min_size) + params.min_size;
-        let height =
-            rand::random::<f32>() * (params.max_height - params.min_height) + params.min_height;
+        let width = rng.gen::<f32>() * (params.max_size - params.min_size) + params.min_size;
+        let depth = rng.gen::<f32>() * (params.max_size - params.min_size) + params.min_size;
+        let height = rng.gen::<f32>() * (params.max_height - params.min_height) + params.min_height;
         let room = RoomPrototype {
+            id: String::new(),
             name: "Unnamed".to_string(),
             description: "None".to_string(),
             color: Color::rgb(1.0, 1.0, 1.0),
@@ -503,7 +630,7 @@ pub fn generate(params: &Parameters) -> LevelPrototype {
             edges: Vec::new(),
             props: Vec::new(),
         };
-        if rand::random::<f32>() < params.clone_probability {
+        if rng.gen::<f32>() < params.clone_probability {
             rooms.push(room.clone());
             size -= 1;
         }
@@ -514,43 +641,276 @@ pub fn generate(params: &Parameters) -> LevelPrototype {
     }
 
     let len = rooms.len();
-
     for (i, room) in rooms.iter_mut().enumerate() {
-        let n = 1 + rand::random::<usize>() % 3;
-        while room.doors.len() < n {
-            let door = rand::random();
-            if !room.doors.contains(&door) {
-                room.doors.insert(door);
+        room.id = format!("room{}", i);
+    }
+
+    for i in 1..len {
+        let candidates = (0..i)
+            .filter(|&j| !free_doors(&rooms[j]).is_empty())
+            .collect::<Vec<_>>();
+        let j = candidates[rng.gen::<usize>() % candidates.len()];
+        connect(&mut rooms, i, j, rng);
+    }
+
+    for i in 0..len {
+        let n = 1 + rng.gen::<usize>() % 3;
+        let mut attempts = 0;
+        while rooms[i].doors.len() < n && attempts < len {
+            attempts += 1;
+            let j = rng.gen::<usize>() % len;
+            let connected = rooms[i].edges.iter().any(|edge| edge.room == rooms[j].id);
+            if j != i && !connected && !free_doors(&rooms[j]).is_empty() {
+                connect(&mut rooms, i, j, rng);
             }
         }
-        let mut edges = Vec::new();
-        for &from in &room.doors {
-            let mut index;
-            loop {
-                index = rand::random::<usize>() % len;
-                if index != i {
+    }
+
+    for room in &mut rooms {
+        let w = room.width / 2.0;
+        let d = room.depth / 2.0;
+        let bounds = Aabb::new(Vec2::new(-w, -d), Vec2::new(w, d));
+        let mut occupied = corridors(room);
+        // nothing to place, and no random numbers to draw for it
+        if params.props.is_empty() {
+            continue;
+        }
+        let n = rng.gen_range(params.min_props, params.max_props + 1);
+        for _ in 0..n {
+            let prop = rng.gen::<usize>() % params.props.len();
+            let (ref name, footprint) = params.props[prop];
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                let x = rng.gen::<f32>() * room.width - w;
+                let y = rng.gen::<f32>() * room.depth - d;
+                let r = rng.gen::<f32>() * PI;
+                let position = Vec2::new(x, y);
+                let aabb = footprint.transform(position, r);
+                if bounds.contains(&aabb) && !occupied.iter().any(|other| other.overlaps(&aabb)) {
+                    occupied.push(aabb);
+                    let (status, mass) = if DYNAMIC_PROPS.contains(&name.as_str()) {
+                        (Status::Dynamic, DYNAMIC_PROP_MASS)
+                    } else {
+                        (Status::Static, prop_mass())
+                    };
+                    room.props.push(PropPrototype {
+                        name: name.clone(),
+                        position,
+                        rotation: r,
+                        status,
+                        mass,
+                        material: None,
+                    });
                     break;
                 }
             }
-            let to = rand::random::<Door>();
-            edges.push(EdgePrototype { index, from, to });
         }
-        room.edges = edges;
+    }
 
-        let n = rand::random::<usize>() % (params.max_props - params.min_props) + params.min_props;
-        for _ in 0..n {
-            let x = rand::random::<f32>() * room.width - room.width / 2.0;
-            let y = rand::random::<f32>() * room.depth - room.depth / 2.0;
-            let r = rand::random::<f32>() * PI;
-            let prop = rand::random::<usize>() % params.props.len();
-            let name = params.props[prop].clone();
-            room.props.push(PropPrototype {
-                name,
-                position: Vec2::new(x, y),
-                rotation: r,
-            });
+    let mut level = LevelPrototype {
+        start: rooms[0].id.clone(),
+        goal: None,
+        rooms,
+    };
+    if params.goal {
+        let goal = farthest(&level, 0);
+        level.goal = Some(level.rooms[goal].id.clone());
+    }
+    level
+}
+
+// a strip as wide as the door from every door to the center of the room, so
+// that any two doors are connected through the middle
+fn corridors(room: &RoomPrototype) -> Vec<Aabb> {
+    let w = room.width / 2.0;
+    let d = room.depth / 2.0;
+    let c = CORRIDOR_WIDTH / 2.0;
+    Door::ALL
+        .iter()
+        .filter(|door| room.doors.contains(door))
+        .map(|door| match door {
+            Door::North => Aabb::new(Vec2::new(-c, -d), Vec2::new(c, c)),
+            Door::South => Aabb::new(Vec2::new(-c, -c), Vec2::new(c, d)),
+            Door::East => Aabb::new(Vec2::new(-w, -c), Vec2::new(c, c)),
+            Door::West => Aabb::new(Vec2::new(-c, -c), Vec2::new(w, c)),
+        })
+        .collect()
+}
+
+fn free_doors(room: &RoomPrototype) -> Vec<Door> {
+    Door::ALL
+        .iter()
+        .copied()
+        .filter(|door| !room.doors.contains(door))
+        .collect()
+}
+
+fn connect<R: Rng + ?Sized>(rooms: &mut [RoomPrototype], a: usize, b: usize, rng: &mut R) {
+    let doors = free_doors(&rooms[a]);
+    let from = doors[rng.gen::<usize>() % doors.len()];
+    let doors = free_doors(&rooms[b]);
+    let to = doors[rng.gen::<usize>() % doors.len()];
+
+    let id = rooms[b].id.clone();
+    rooms[a].doors.insert(from);
+    rooms[a].edges.push(EdgePrototype { room: id, from, to });
+
+    let id = rooms[a].id.clone();
+    rooms[b].doors.insert(to);
+    rooms[b].edges.push(EdgePrototype {
+        room: id,
+        from: to,
+        to: from,
+    });
+}
+
+fn farthest(level: &LevelPrototype, start: usize) -> usize {
+    let mut visited = vec![false; level.rooms.len()];
+    let mut queue = VecDeque::new();
+    let mut last = start;
+    visited[start] = true;
+    queue.push_back(start);
+    while let Some(i) = queue.pop_front() {
+        last = i;
+        for edge in &level.rooms[i].edges {
+            if let Some(j) = level.index(&edge.room) {
+                if !visited[j] {
+                    visited[j] = true;
+                    queue.push_back(j);
+                }
+            }
+        }
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(seed: u64) -> Parameters {
+        Parameters {
+            seed,
+            size: 12,
+            min_size: 4.0,
+            max_size: 16.0,
+            min_height: 2.0,
+            max_height: 2.0,
+            clone_probability: 0.5,
+            goal: true,
+            min_props: 0,
+            max_props: 3,
+            props: vec![
+                (
+                    "bed".to_string(),
+                    Aabb::new(Vec2::new(-1.0, -0.5), Vec2::new(1.0, 0.5)),
+                ),
+                (
+                    "chair".to_string(),
+                    Aabb::new(Vec2::new(-0.3, -0.3), Vec2::new(0.3, 0.3)),
+                ),
+            ],
         }
     }
 
-    LevelPrototype { start, rooms }
+    #[test]
+    fn same_seed_same_level() {
+        assert_eq!(generate(&params(42)), generate(&params(42)));
+    }
+
+    #[test]
+    fn different_seed_different_level() {
+        assert_ne!(generate(&params(42)), generate(&params(43)));
+    }
+
+    fn reachable(level: &LevelPrototype) -> usize {
+        let mut visited = vec![false; level.rooms.len()];
+        let mut stack = vec![level.index(&level.start).unwrap()];
+        while let Some(i) = stack.pop() {
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            for edge in &level.rooms[i].edges {
+                stack.push(level.index(&edge.room).unwrap());
+            }
+        }
+        visited.iter().filter(|&&visited| visited).count()
+    }
+
+    #[test]
+    fn generated_levels_are_valid() {
+        for seed in 0..100 {
+            for &size in &[1, 2, 3, 5, 12, 40] {
+                let level = generate(&Parameters {
+                    size,
+                    ..params(seed)
+                });
+                let context = format!("seed {} size {}", seed, size);
+                assert_eq!(level.rooms.len(), size, "{}", context);
+                assert_eq!(crate::level::validate(&level), Ok(()), "{}", context);
+
+                for room in &level.rooms {
+                    for edge in &room.edges {
+                        let target = &level.rooms[level.index(&edge.room).unwrap()];
+                        let back = target.edges.iter().any(|back| {
+                            back.room == room.id && back.from == edge.to && back.to == edge.from
+                        });
+                        assert!(
+                            back,
+                            "{}: {:?} from {:?} is one way",
+                            context, edge, room.id
+                        );
+                    }
+                }
+                assert_eq!(reachable(&level), size, "{}", context);
+            }
+        }
+    }
+
+    #[test]
+    fn props_stay_clear() {
+        let footprints = params(0).props.into_iter().collect::<HashMap<_, _>>();
+        let mut placed = 0;
+        for seed in 0..100 {
+            let level = generate(&Parameters {
+                min_props: 2,
+                max_props: 6,
+                ..params(seed)
+            });
+            assert_eq!(
+                crate::level::validate_props(&level, &footprints),
+                Ok(()),
+                "seed {}",
+                seed
+            );
+
+            for room in &level.rooms {
+                let aabbs = room
+                    .props
+                    .iter()
+                    .map(|prop| footprints[&prop.name].transform(prop.position, prop.rotation))
+                    .collect::<Vec<_>>();
+                placed += aabbs.len();
+                for (i, a) in aabbs.iter().enumerate() {
+                    for b in &aabbs[i + 1..] {
+                        assert!(
+                            !a.overlaps(b),
+                            "seed {}: props overlap in {}",
+                            seed,
+                            room.id
+                        );
+                    }
+                    for corridor in corridors(room) {
+                        assert!(
+                            !a.overlaps(&corridor),
+                            "seed {}: prop blocks a door of {}",
+                            seed,
+                            room.id
+                        );
+                    }
+                }
+            }
+        }
+        assert!(placed > 0);
+    }
 }
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use hashbrown::HashSet;
+
+use crate::character::Character;
+use crate::level;
+use crate::phys::*;
+use crate::proc::{self, RoomSensor};
+use crate::room::*;
+
+pub struct LevelWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+impl LevelWatcher {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        Self {
+            path,
+            modified,
+            timer: Timer::from_seconds(0.5, true),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+pub fn level_reload_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut watcher: ResMut<LevelWatcher>,
+    current: Res<CurrentRoom>,
+    mut rooms: Query<With<RoomMarker, (Entity, &RoomId, &Props)>>,
+    doorsets: Query<&DoorSet>,
+    mut room_sensors: Query<With<RoomSensor, Entity>>,
+    mut joints: Query<(Entity, &Joint)>,
+    mut players: Query<With<Character, Mut<RigidBody>>>,
+) {
+    watcher.timer.tick(time.delta_seconds);
+    if !watcher.timer.finished {
+        return;
+    }
+    let modified = modified(&watcher.path);
+    if modified == watcher.modified {
+        return;
+    }
+    watcher.modified = modified;
+
+    let mut level = match level::load(&watcher.path) {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return;
+        }
+    };
+    if !level::check(&level) {
+        return;
+    }
+    level::check_props(&level, &proc::footprints(&assets, &mut meshes, &level));
+
+    let mut despawned = HashSet::new();
+    let mut current_id = None;
+    for (e, id, props) in &mut rooms.iter() {
+        if e == current.entity {
+            current_id = Some(id.0.clone());
+        }
+        // props and doors are children of the room and go along with it
+        despawned.extend(props.vec.iter().copied());
+        if let Ok(doorset) = doorsets.get::<DoorSet>(e) {
+            despawned.extend(doorset.vec.iter().copied());
+        }
+        commands.despawn_recursive(e);
+        despawned.insert(e);
+    }
+    for e in &mut room_sensors.iter() {
+        commands.despawn_recursive(e);
+        despawned.insert(e);
+    }
+    // door hinges live on the doors themselves, which are already gone
+    for (e, joint) in &mut joints.iter() {
+        let (body1, body2) = joint.bodies();
+        if despawned.contains(&e) {
+            continue;
+        }
+        if despawned.contains(&body1) || despawned.contains(&body2) {
+            commands.despawn(e);
+        }
+    }
+
+    if let Some(id) = current_id {
+        if level.index(&id).is_some() {
+            level.start = id;
+        }
+    }
+    let room = &level.rooms[level.index(&level.start).unwrap()];
+    let w = room.width / 2.0 - 0.5;
+    let d = room.depth / 2.0 - 0.5;
+    for mut body in &mut players.iter() {
+        let x = body.position.x().max(-w).min(w);
+        let y = body.position.y().max(-d).min(d);
+        body.position = Vec2::new(x, y);
+        body.velocity = Vec2::zero();
+    }
+
+    proc::spawn(&mut commands, &assets, &mut meshes, &mut materials, &level);
+}
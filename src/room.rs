@@ -10,8 +10,14 @@ pub struct CurrentRoom {
 #[derive(Default, Debug)]
 pub struct ActiveRoom;
 
+#[derive(Debug, Clone, Copy)]
+pub struct RoomChanged(pub Entity);
+
 pub struct RoomMarker;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoomId(pub String);
+
 #[derive(Default, Debug)]
 pub struct DoorSet {
     pub vec: Vec<Entity>,
@@ -20,6 +26,7 @@ pub struct DoorSet {
 #[derive(Bundle)]
 pub struct RoomBundle {
     pub marker: RoomMarker,
+    pub id: RoomId,
     pub name: Name,
     pub body: RigidBody,
     pub props: Props,
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::character::Character;
+use crate::cli::{Mode, Options};
+use crate::phys::*;
+use crate::proc::{Connection, Door};
+use crate::room::*;
+
+pub const SAVE: &str = "save.ron";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::Error),
+}
+
+impl SaveError {
+    // there is just no save yet
+    pub fn is_missing(&self) -> bool {
+        match self {
+            SaveError::Io(_, err) => err.kind() == io::ErrorKind::NotFound,
+            SaveError::Parse(..) => false,
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(path, err) => {
+                write!(f, "could not read save file {}: {}", path.display(), err)
+            }
+            SaveError::Parse(path, err) => {
+                write!(f, "could not parse save file {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DoorState {
+    pub room: String,
+    pub door: Door,
+    pub open: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub level: String,
+    pub room: String,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub yrot: f32,
+    pub xrot: f32,
+    pub doors: Vec<DoorState>,
+}
+
+impl SaveGame {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SaveError> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|err| SaveError::Io(path.into(), err))?;
+        ron::de::from_reader(file).map_err(|err| SaveError::Parse(path.into(), err))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let s = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+        fs::write(path, s)
+    }
+}
+
+pub fn level_key(mode: &Mode) -> String {
+    match mode {
+        Mode::Level(path) => path.display().to_string(),
+        Mode::Generate { seed, size } => format!("generate:{}:{}", seed, size),
+        Mode::Faux => "faux".to_string(),
+    }
+}
+
+#[derive(Default)]
+pub struct AutosaveState {
+    reader: EventReader<RoomChanged>,
+}
+
+pub fn autosave_system(
+    options: Res<Options>,
+    events: Res<Events<RoomChanged>>,
+    mut state: ResMut<AutosaveState>,
+    rooms: Query<&RoomId>,
+    mut players: Query<(&Character, &RigidBody)>,
+    mut connections: Query<&Connection>,
+) {
+    let mut room = None;
+    for &RoomChanged(e) in state.reader.iter(&events) {
+        room = Some(e);
+    }
+    let room = match room.and_then(|e| rooms.get::<RoomId>(e).ok()) {
+        Some(id) => id.0.clone(),
+        None => return,
+    };
+
+    let mut doors = Vec::new();
+    for conn in &mut connections.iter() {
+        if let Ok(id) = rooms.get::<RoomId>(conn.this) {
+            doors.push(DoorState {
+                room: id.0.clone(),
+                door: conn.door,
+                open: conn.open,
+            });
+        }
+    }
+
+    for (controller, body) in &mut players.iter() {
+        let (yrot, xrot) = controller.rotation();
+        let save = SaveGame {
+            level: level_key(&options.mode),
+            room: room.clone(),
+            position: body.position,
+            velocity: body.velocity,
+            yrot,
+            xrot,
+            doors: doors.clone(),
+        };
+        if let Err(err) = save.save(SAVE) {
+            eprintln!("error: could not save the game: {}", err);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PendingDoors(pub Vec<DoorState>);
+
+// room_system closes every door when it activates the first room, so this
+// waits until a room is active before opening the saved doors
+pub fn restore_doors_system(
+    mut pending: ResMut<PendingDoors>,
+    mut active: Query<With<ActiveRoom, Entity>>,
+    rooms: Query<&RoomId>,
+    mut connections: Query<(Entity, Mut<Connection>, Mut<Joint>)>,
+    bodies: Query<Mut<RigidBody>>,
+) {
+    if pending.0.is_empty() || active.iter().iter().next().is_none() {
+        return;
+    }
+    for (e, mut conn, mut joint) in &mut connections.iter() {
+        let open = match rooms.get::<RoomId>(conn.this) {
+            Ok(id) => pending
+                .0
+                .iter()
+                .find(|state| state.room == id.0 && state.door == conn.door)
+                .map(|state| state.open),
+            Err(_) => None,
+        };
+        if let Some(open) = open {
+            let mut body = bodies.get_mut::<RigidBody>(e).unwrap();
+            conn.snap_open(&mut body, &mut joint, open);
+        }
+    }
+    pending.0.clear();
+}